@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, Cursor},
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+
+use crate::sprite_sheet::SpriteSheet;
+
+/// Sprite sheet used when a sprite references one that does not exist
+pub const FALLBACK_SPRITE_SHEET: &str = "penguin";
+
+/// Assets referenced by name, loaded from an assets directory with the embedded ones as fallback
+pub struct Assets {
+    sprite_sheets: HashMap<String, SpriteSheet>,
+    textures: HashMap<String, RgbaImage>,
+}
+
+/// Get the names and paths of the PNG files of `directory`, which may not exist
+fn get_png_files(directory: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut png_files = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "png") {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+            png_files.push((name.to_owned(), path));
+        }
+    }
+    Ok(png_files)
+}
+
+impl Assets {
+    /// Load the embedded assets, then the ones of `directory` if any, overriding embedded ones
+    /// with the same name.
+    ///
+    /// Sprite sheets are loaded from "`directory`/sprites/NAME.png", with their optional metadata
+    /// file "`directory`/sprites/NAME.toml", and textures from "`directory`/textures/NAME.png".
+    pub fn load(directory: Option<&Path>) -> io::Result<Self> {
+        let penguin = SpriteSheet::new(
+            Cursor::new(include_bytes!("../assets/sprites/penguin.png")),
+            Some(include_str!("../assets/sprites/penguin.toml")),
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut assets = Self {
+            sprite_sheets: HashMap::from([(String::from(FALLBACK_SPRITE_SHEET), penguin)]),
+            textures: HashMap::new(),
+        };
+        if let Some(directory) = directory {
+            assets.load_sprite_sheets(&directory.join("sprites"))?;
+            assets.load_textures(&directory.join("textures"))?;
+        }
+        Ok(assets)
+    }
+
+    fn load_sprite_sheets(&mut self, directory: &Path) -> io::Result<()> {
+        for (name, path) in get_png_files(directory)? {
+            let metadata = match fs::read_to_string(path.with_extension("toml")) {
+                Ok(metadata) => Some(metadata),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            let reader = BufReader::new(File::open(&path)?);
+            let sprite_sheet = SpriteSheet::new(reader, metadata.as_deref()).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), e),
+                )
+            })?;
+            self.sprite_sheets.insert(name, sprite_sheet);
+        }
+        Ok(())
+    }
+
+    fn load_textures(&mut self, directory: &Path) -> io::Result<()> {
+        for (name, path) in get_png_files(directory)? {
+            let texture = image::open(&path)
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: {}", path.display(), e),
+                    )
+                })?
+                .into_rgba8();
+            self.textures.insert(name, texture);
+        }
+        Ok(())
+    }
+
+    pub fn has_sprite_sheet(&self, name: &str) -> bool {
+        self.sprite_sheets.contains_key(name)
+    }
+
+    /// Get the sprite sheet named `name`, or the fallback one if it does not exist
+    pub fn sprite_sheet(&self, name: &str) -> &SpriteSheet {
+        self.sprite_sheets
+            .get(name)
+            .unwrap_or_else(|| &self.sprite_sheets[FALLBACK_SPRITE_SHEET])
+    }
+
+    /// Get the texture named `name`, if it exists
+    pub fn texture(&self, name: &str) -> Option<&RgbaImage> {
+        self.textures.get(name)
+    }
+}
@@ -0,0 +1,195 @@
+use std::{collections::HashSet, f64, fmt::Debug, iter::Sum, ops::Div, time::Instant};
+
+use common::{get_normalized_radians_angle, map::Map};
+use crossterm::Result;
+use nalgebra::Point2;
+
+use crate::{rendering::render_frame, Raycasting};
+
+/// Frames spent going from a cell of the camera path to the next one
+const FRAMES_PER_CELL: usize = 8;
+
+/// Simulated time between two frames, advancing the animations and the flickering lights
+const FRAME_TIME: f64 = 1.0 / 60.0;
+
+/// Get the centers of the cells visited by a depth-first walk through the walkable cells from
+/// `start`, going back through the visited ones when stuck so that consecutive cells are adjacent
+fn get_camera_path(map: &Map, start: &Point2<f64>) -> Vec<Point2<f64>> {
+    let start = (start.x.floor() as i64, start.y.floor() as i64);
+    let mut visited = HashSet::from([start]);
+    let mut stack = vec![start];
+    let mut path = vec![start];
+    while let Some(&(x, y)) = stack.last() {
+        let next = [(x + 1, y), (x, y + 1), (x - 1, y), (x, y - 1)]
+            .into_iter()
+            .find(|&(x, y)| {
+                let is_inside =
+                    x >= 0 && y >= 0 && x < map.width() as i64 && y < map.height() as i64;
+                is_inside && !map.cell_type(x, y).is_wall() && !visited.contains(&(x, y))
+            });
+        match next {
+            Some(cell) => {
+                visited.insert(cell);
+                stack.push(cell);
+                path.push(cell);
+            }
+            None => {
+                stack.pop();
+                if let Some(&cell) = stack.last() {
+                    path.push(cell);
+                }
+            }
+        }
+    }
+    path.into_iter()
+        .map(|(x, y)| Point2::new(x as f64 + 0.5, y as f64 + 0.5))
+        .collect()
+}
+
+/// Minimum, average and 99th percentile of values measured on each frame
+struct Statistics<T> {
+    min: T,
+    average: T,
+    p99: T,
+}
+
+impl<T: Copy + Default + Ord + Sum + Div<u32, Output = T>> Statistics<T> {
+    fn new(values: &mut [T]) -> Self {
+        values.sort_unstable();
+        let p99_index = (values.len() * 99).div_ceil(100).saturating_sub(1);
+        Self {
+            min: values.first().copied().unwrap_or_default(),
+            average: values.iter().copied().sum::<T>() / values.len().max(1) as u32,
+            p99: values.get(p99_index).copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl<T: Debug> Statistics<T> {
+    fn print(&self, name: &str, unit: &str) {
+        let format = |value: &T| format!("{:.1?}{}", value, unit);
+        println!(
+            "{:<18} {:>12} {:>12} {:>12}",
+            name,
+            format(&self.min),
+            format(&self.average),
+            format(&self.p99)
+        );
+    }
+}
+
+/// Render `frame_count` frames of the camera going through the map, then print the times spent
+/// casting rays, projecting sprites and writing the terminal output, and the bytes written.
+///
+/// The window of `raycasting` should be a [`Window::headless`](crate::window::Window::headless)
+/// one.
+pub fn run_bench(raycasting: &mut Raycasting, frame_count: usize) -> Result<()> {
+    let spawn = raycasting.player.position;
+    let path = get_camera_path(&raycasting.map, &spawn);
+    let spawn_eye_height =
+        raycasting.player.camera().eye_height - raycasting.map.cell_type_at(&spawn).floor_height;
+    let mut ray_casting = Vec::with_capacity(frame_count);
+    let mut sprite_projection = Vec::with_capacity(frame_count);
+    let mut terminal_output = Vec::with_capacity(frame_count);
+    let mut terminal_output_bytes = Vec::with_capacity(frame_count);
+    for frame in 0..frame_count {
+        // Follow the path in a loop, as it ends where it starts, or turn around in place if it
+        // is a single cell
+        let segment_count = path.len() - 1;
+        let segment = frame / FRAMES_PER_CELL % segment_count.max(1);
+        let progress = (frame % FRAMES_PER_CELL) as f64 / FRAMES_PER_CELL as f64;
+        let camera = &mut raycasting.camera;
+        if segment_count == 0 {
+            camera.position = path[0];
+            camera.angle = get_normalized_radians_angle(progress * f64::consts::TAU);
+        } else {
+            let (from, to) = (path[segment], path[segment + 1]);
+            camera.position = from + (to - from) * progress;
+            // The map y axis goes down
+            camera.angle = get_normalized_radians_angle((from.y - to.y).atan2(to.x - from.x));
+        }
+        camera.pitch = 0.2 * (frame as f64 / 50.0).sin();
+        camera.eye_height =
+            spawn_eye_height + raycasting.map.cell_type_at(&camera.position).floor_height;
+        for sprite in &mut raycasting.sprites {
+            sprite.animation.time += FRAME_TIME;
+        }
+        for wall_sprite in &mut raycasting.wall_sprites {
+            wall_sprite.animation.time += FRAME_TIME;
+        }
+        raycasting.light_map.update(FRAME_TIME);
+
+        let times = render_frame(raycasting);
+        ray_casting.push(times.ray_casting);
+        sprite_projection.push(times.sprite_projection);
+        let start = Instant::now();
+        raycasting.window.set_pixels(
+            raycasting.framebuffer.colors(),
+            raycasting.framebuffer.depths(),
+        );
+        raycasting.window.redraw()?;
+        terminal_output.push(start.elapsed());
+        terminal_output_bytes.push(raycasting.window.frame_bytes() as u32);
+    }
+    println!(
+        "{} frames of {}x{} pixels, {} rendering threads",
+        frame_count,
+        raycasting.window.width(),
+        raycasting.window.height(),
+        raycasting.render_thread_count
+    );
+    println!("{:<18} {:>12} {:>12} {:>12}", "", "min", "avg", "p99");
+    for (name, durations) in [
+        ("ray casting", &mut ray_casting),
+        ("sprite projection", &mut sprite_projection),
+        ("terminal output", &mut terminal_output),
+    ] {
+        Statistics::new(durations).print(name, "");
+    }
+    // Only the cells that changed since the previous frame are written
+    Statistics::new(&mut terminal_output_bytes).print("bytes per frame", " B");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_camera_path() {
+        let map = Map::new(
+            vec![
+                vec![1, 1, 1, 1, 1],
+                vec![1, 0, 0, 0, 1],
+                vec![1, 0, 1, 1, 1],
+                vec![1, 1, 1, 1, 1],
+            ],
+            1,
+        )
+        .unwrap();
+        let path: Vec<_> = get_camera_path(&map, &Point2::new(2.2, 1.7))
+            .into_iter()
+            .map(|point| (point.x, point.y))
+            .collect();
+        assert_eq!(
+            path,
+            [
+                (2.5, 1.5),
+                (3.5, 1.5),
+                (2.5, 1.5),
+                (1.5, 1.5),
+                (1.5, 2.5),
+                (1.5, 1.5),
+                (2.5, 1.5)
+            ]
+        );
+
+        let mut durations: Vec<_> = (1..=200).rev().map(Duration::from_millis).collect();
+        let statistics = Statistics::new(&mut durations);
+        assert_eq!(statistics.min, Duration::from_millis(1));
+        assert_eq!(statistics.p99, Duration::from_millis(198));
+        assert_eq!(statistics.average, Duration::from_micros(100_500));
+    }
+}
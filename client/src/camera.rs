@@ -0,0 +1,58 @@
+use std::f64;
+
+use common::get_normalized_radians_angle;
+use nalgebra::Point2;
+
+/// Point of view used for rendering
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Point2<f64>,
+    pub angle: f64,
+    pub pitch: f64,
+    pub eye_height: f64,
+    pub horizontal_fov: f64,
+}
+
+impl Camera {
+    /// Get the camera between `previous` and `current`, `alpha` being in [0; 1], at the current
+    /// position if `has_teleported` as the camera did not move continuously
+    pub fn interpolate(
+        previous: &Camera,
+        current: &Camera,
+        alpha: f64,
+        has_teleported: bool,
+    ) -> Camera {
+        let lerp = |a: f64, b: f64| a + (b - a) * alpha;
+        let mut angle_difference = current.angle - previous.angle;
+        if angle_difference > f64::consts::PI {
+            angle_difference -= f64::consts::TAU;
+        } else if angle_difference < -f64::consts::PI {
+            angle_difference += f64::consts::TAU;
+        }
+        let position = if has_teleported {
+            current.position
+        } else {
+            previous.position + (current.position - previous.position) * alpha
+        };
+        Camera {
+            position,
+            angle: get_normalized_radians_angle(previous.angle + angle_difference * alpha),
+            pitch: lerp(previous.pitch, current.pitch),
+            eye_height: lerp(previous.eye_height, current.eye_height),
+            horizontal_fov: current.horizontal_fov,
+        }
+    }
+
+    /// Get angle to position in [-pi; pi[
+    pub fn get_angle_to(&self, position: &Point2<f64>) -> f64 {
+        let camera_to_position = position - self.position;
+        let camera_to_position_world_angle = (-camera_to_position.y).atan2(camera_to_position.x);
+        let angle_from_camera =
+            get_normalized_radians_angle(camera_to_position_world_angle - self.angle);
+        if angle_from_camera >= f64::consts::PI {
+            angle_from_camera - f64::consts::TAU
+        } else {
+            angle_from_camera
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::env;
+
+use clap::ValueEnum;
+use crossterm::style::Color;
+use nalgebra::Vector2;
+use serde::Deserialize;
+
+/// Characters from the darkest to the brightest luminance
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Channel levels of the 6x6x6 color cube of the 256 colors palette, starting at index 16
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// 4x4 Bayer matrix, giving the order in which pixels of a pattern switch to the next color
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Amplitude of the offset added to the channels before picking a color of the 16 colors palette,
+/// roughly the distance between its levels
+const DITHER_SPREAD: f64 = 96.0;
+
+/// The 16 colors of the terminal, with their usual xterm values
+const ANSI_16_COLORS: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::DarkRed, [205, 0, 0]),
+    (Color::DarkGreen, [0, 205, 0]),
+    (Color::DarkYellow, [205, 205, 0]),
+    (Color::DarkBlue, [0, 0, 238]),
+    (Color::DarkMagenta, [205, 0, 205]),
+    (Color::DarkCyan, [0, 205, 205]),
+    (Color::Grey, [229, 229, 229]),
+    (Color::DarkGrey, [127, 127, 127]),
+    (Color::Red, [255, 0, 0]),
+    (Color::Green, [0, 255, 0]),
+    (Color::Yellow, [255, 255, 0]),
+    (Color::Blue, [92, 92, 255]),
+    (Color::Magenta, [255, 0, 255]),
+    (Color::Cyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+/// Colors the terminal can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+pub enum ColorMode {
+    /// 24-bit colors
+    #[serde(rename = "truecolor")]
+    #[value(name = "truecolor")]
+    TrueColor,
+    /// Nearest color of the 256 colors palette
+    #[serde(rename = "256")]
+    #[value(name = "256")]
+    Ansi256,
+    /// 16 colors palette, dithered
+    #[serde(rename = "16")]
+    #[value(name = "16")]
+    Ansi16,
+    /// No colors, characters shaded by luminance
+    #[serde(rename = "ascii")]
+    #[value(name = "ascii")]
+    Ascii,
+    /// No colors, braille dots on the edges of walls and sprites, with 2x4 pixels per cell
+    #[serde(rename = "braille")]
+    #[value(name = "braille")]
+    Braille,
+}
+
+fn get_squared_distance(a: [u8; 3], b: [u8; 3]) -> i32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+        .sum()
+}
+
+/// Get the red, green and blue channels of `color`, if it is a 24-bit one
+pub fn get_rgb(color: Color) -> Option<[u8; 3]> {
+    match color {
+        Color::Rgb { r, g, b } => Some([r, g, b]),
+        _ => None,
+    }
+}
+
+/// Get the perceived brightness of `rgb`, from 0 to 1
+pub fn get_luminance([r, g, b]: [u8; 3]) -> f64 {
+    (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0
+}
+
+/// Get the character whose density best matches `luminance`, from 0 to 1
+pub fn get_ascii_char(luminance: f64) -> char {
+    let index = (luminance.clamp(0.0, 1.0) * (ASCII_RAMP.len() - 1) as f64).round() as usize;
+    ASCII_RAMP[index] as char
+}
+
+/// Get the index of the nearest color of the 256 colors palette, among its color cube and its
+/// grayscale ramp
+fn get_ansi_256(rgb: [u8; 3]) -> u8 {
+    let cube_indices = rgb.map(|channel| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &level)| (level as i32 - channel as i32).abs())
+            .unwrap()
+            .0
+    });
+    let cube_rgb = cube_indices.map(|index| CUBE_LEVELS[index]);
+    let average = rgb.iter().map(|&channel| channel as u32).sum::<u32>() / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_index;
+    if get_squared_distance(rgb, [gray_level; 3]) < get_squared_distance(rgb, cube_rgb) {
+        232 + gray_index
+    } else {
+        16 + 36 * cube_indices[0] as u8 + 6 * cube_indices[1] as u8 + cube_indices[2] as u8
+    }
+}
+
+/// Get the nearest color of the 16 colors palette once offset by the Bayer matrix value of the
+/// pixel at (x, y), so that areas between two colors mix them
+fn get_ansi_16(rgb: [u8; 3], x: usize, y: usize) -> Color {
+    let threshold = (BAYER_MATRIX[y % 4][x % 4] as f64 + 0.5) / 16.0 - 0.5;
+    let dithered =
+        rgb.map(|channel| (channel as f64 + threshold * DITHER_SPREAD).clamp(0.0, 255.0) as u8);
+    ANSI_16_COLORS
+        .iter()
+        .min_by_key(|(_, palette_rgb)| get_squared_distance(dithered, *palette_rgb))
+        .unwrap()
+        .0
+}
+
+impl ColorMode {
+    /// Guess the color mode of the terminal from the `COLORTERM` and `TERM` environment variables
+    pub fn detect() -> Self {
+        let color_term = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        Self::from_env(&color_term, &term)
+    }
+
+    fn from_env(color_term: &str, term: &str) -> Self {
+        if color_term == "truecolor" || color_term == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else if term == "dumb" {
+            Self::Ascii
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Get the number of pixels drawn in each terminal cell, horizontally and vertically
+    pub fn cell_size(self) -> Vector2<u16> {
+        match self {
+            Self::Braille => Vector2::new(2, 4),
+            _ => Vector2::new(1, 2),
+        }
+    }
+
+    /// Convert `color` of the pixel at (x, y) to a color of this mode.
+    ///
+    /// Colors are unchanged in the modes that do not use them.
+    pub fn convert(self, color: Color, x: usize, y: usize) -> Color {
+        match (self, get_rgb(color)) {
+            (Self::Ansi256, Some(rgb)) => Color::AnsiValue(get_ansi_256(rgb)),
+            (Self::Ansi16, Some(rgb)) => get_ansi_16(rgb, x, y),
+            _ => color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_mode() {
+        assert_eq!(
+            ColorMode::from_env("truecolor", "xterm"),
+            ColorMode::TrueColor
+        );
+        assert_eq!(
+            ColorMode::from_env("", "screen-256color"),
+            ColorMode::Ansi256
+        );
+        assert_eq!(ColorMode::from_env("", "xterm"), ColorMode::Ansi16);
+        assert_eq!(ColorMode::from_env("", "dumb"), ColorMode::Ascii);
+
+        assert_eq!(get_ansi_256([255, 0, 0]), 196);
+        assert_eq!(get_ansi_256([128, 128, 128]), 244);
+        assert_eq!(get_ansi_16([0, 0, 0], 1, 2), Color::Black);
+        assert_eq!(get_ansi_16([255, 255, 255], 3, 0), Color::White);
+        // Half gray mixes black and gray pixels
+        let colors: Vec<_> = (0..16)
+            .map(|i| get_ansi_16([64, 64, 64], i % 4, i / 4))
+            .collect();
+        assert!(colors.contains(&Color::Black));
+        assert!(colors.contains(&Color::DarkGrey));
+
+        assert_eq!(get_ascii_char(0.0), ' ');
+        assert_eq!(get_ascii_char(1.0), '@');
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de, Deserialize};
+
+use crate::{
+    assets::FALLBACK_SPRITE_SHEET,
+    color_mode::ColorMode,
+    input::{KeyBindings, MouseConfig},
+};
+
+/// Highest resolution width and height, which the braille mode doubles
+pub const MAX_RESOLUTION: u16 = 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Resolution {
+    pub height: u16,
+    pub width: u16,
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self {
+            height: 45,
+            width: 80,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Horizontal field of view in degrees
+    pub fov: f64,
+    /// Vertical oscillation of the view while walking
+    pub view_bob: bool,
+    /// Crosshair and row of text about the game above the view
+    pub hud: bool,
+    pub resolution: Resolution,
+    pub server_address: Option<String>,
+    /// Level file, the embedded one being used if missing
+    pub map: Option<PathBuf>,
+    /// Directory containing the assets, in addition to the embedded ones
+    pub assets_directory: Option<PathBuf>,
+    /// Sprite sheet seen by the other players in multiplayer
+    pub skin: String,
+    /// Colors used to draw, detected from the terminal if missing
+    pub color_mode: Option<ColorMode>,
+    pub key_bindings: KeyBindings,
+    pub mouse: MouseConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            fov: 60.0,
+            view_bob: true,
+            hud: true,
+            resolution: Resolution::default(),
+            server_address: None,
+            map: None,
+            assets_directory: None,
+            skin: String::from(FALLBACK_SPRITE_SHEET),
+            color_mode: None,
+            key_bindings: KeyBindings::default(),
+            mouse: MouseConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Get the default config file path, eg. "~/.config/raycasting-term/config.toml" on Linux
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|path| path.join("raycasting-term").join("config.toml"))
+    }
+
+    /// Load the config file at the default path, a missing file giving the default config
+    pub fn load_default() -> io::Result<Self> {
+        match Self::default_path() {
+            Some(path) => match Self::load(&path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+                result => result,
+            },
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the config file at `path`
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })
+    }
+
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        let config: Self = toml::from_str(content)?;
+        // Rays are cast from one edge of the field of view to the other, which needs 2 columns
+        let resolution = &config.resolution;
+        if !(2..=MAX_RESOLUTION).contains(&resolution.width)
+            || !(1..=MAX_RESOLUTION).contains(&resolution.height)
+        {
+            return Err(de::Error::custom(format!(
+                "resolution must be at least 2 pixels wide and 1 pixel high, and at most {} in \
+                 both",
+                MAX_RESOLUTION
+            )));
+        }
+        if !(config.fov > 0.0 && config.fov < 180.0) {
+            return Err(de::Error::custom(
+                "fov must be between 0 and 180 degrees, excluded",
+            ));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+
+    use super::*;
+    use crate::input::{Action, Key};
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            fov = 90.0
+            hud = false
+            server_address = "127.0.0.1:4242"
+            color_mode = "256"
+
+            [resolution]
+            width = 120
+
+            [key_bindings]
+            move_forward = ["z", "PageUp"]
+            quit = ["F10"]
+
+            [mouse]
+            drag_to_turn = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.fov, 90.0);
+        assert!(!config.hud);
+        assert!(config.view_bob);
+        assert_eq!(config.server_address.as_deref(), Some("127.0.0.1:4242"));
+        assert_eq!(config.color_mode, Some(ColorMode::Ansi256));
+        assert_eq!(config.resolution.width, 120);
+        assert_eq!(config.resolution.height, 45);
+        assert_eq!(
+            config.key_bindings.keys(Action::MoveForward),
+            [Key(KeyCode::Char('z')), Key(KeyCode::PageUp)]
+        );
+        assert_eq!(
+            config.key_bindings.keys(Action::Quit),
+            [Key(KeyCode::F(10))]
+        );
+        assert_eq!(
+            config.key_bindings.keys(Action::TurnLeft),
+            [Key(KeyCode::Left)]
+        );
+
+        assert!(config.mouse.enabled);
+        assert!(config.mouse.drag_to_turn);
+
+        assert!(Config::parse(r#"key_bindings = { fly = ["Space"] }"#).is_err());
+        assert!(Config::parse(r#"key_bindings = { quit = ["NotAKey"] }"#).is_err());
+        assert!(Config::parse("resolution = { width = 1 }").is_err());
+        assert!(Config::parse("resolution = { height = 0 }").is_err());
+        assert!(Config::parse("resolution = { width = 40000 }").is_err());
+        assert!(Config::parse("fov = 180.0").is_err());
+        assert!(Config::parse("fov = nan").is_err());
+    }
+}
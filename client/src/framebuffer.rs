@@ -0,0 +1,137 @@
+use std::ops::Range;
+
+use crossterm::style::Color;
+use nalgebra::DMatrix;
+
+/// Pixels of a frame being rendered, with the distance of what is drawn on each so that sprites
+/// are only drawn where nothing nearer already is
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    colors: DMatrix<Color>,
+    depths: DMatrix<f64>,
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl Framebuffer {
+    pub fn new(height: u16, width: u16) -> Self {
+        Self {
+            colors: DMatrix::from_element(height.into(), width.into(), Color::Black),
+            depths: DMatrix::from_element(height.into(), width.into(), f64::INFINITY),
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.colors.ncols() as u16
+    }
+
+    pub fn height(&self) -> u16 {
+        self.colors.nrows() as u16
+    }
+
+    pub fn colors(&self) -> &DMatrix<Color> {
+        &self.colors
+    }
+
+    pub fn depths(&self) -> &DMatrix<f64> {
+        &self.depths
+    }
+
+    /// Split the framebuffer into at most `count` bands of adjacent columns of about the same
+    /// width, from left to right, so that they can be rendered in parallel
+    pub fn split_columns(&mut self, count: usize) -> Vec<Columns<'_>> {
+        let height = self.height();
+        let band_width = usize::from(self.width()).div_ceil(count.max(1)).max(1);
+        // Matrices are stored column by column, so each band is a contiguous slice
+        let band_length = band_width * usize::from(height);
+        self.colors
+            .as_mut_slice()
+            .chunks_mut(band_length)
+            .zip(self.depths.as_mut_slice().chunks_mut(band_length))
+            .enumerate()
+            .map(|(index, (colors, depths))| Columns {
+                start_x: (index * band_width) as u16,
+                height,
+                colors,
+                depths,
+            })
+            .collect()
+    }
+
+    /// Get all the columns of the framebuffer as a single band
+    pub fn columns(&mut self) -> Columns<'_> {
+        self.split_columns(1).pop().unwrap_or(Columns {
+            start_x: 0,
+            height: 0,
+            colors: &mut [],
+            depths: &mut [],
+        })
+    }
+}
+
+/// Band of adjacent columns of a [`Framebuffer`], accessed with framebuffer coordinates
+#[derive(Debug)]
+pub struct Columns<'a> {
+    start_x: u16,
+    height: u16,
+    colors: &'a mut [Color],
+    depths: &'a mut [f64],
+}
+
+impl Columns<'_> {
+    /// Get the framebuffer columns of the band
+    pub fn range(&self) -> Range<u16> {
+        let width = self
+            .colors
+            .len()
+            .checked_div(self.height.into())
+            .unwrap_or(0);
+        self.start_x..self.start_x + width as u16
+    }
+
+    fn index(&self, y: u16, x: u16) -> usize {
+        usize::from(x - self.start_x) * usize::from(self.height) + usize::from(y)
+    }
+
+    pub fn get_pixel(&self, y: u16, x: u16) -> Color {
+        self.colors[self.index(y, x)]
+    }
+
+    pub fn set_pixel(&mut self, y: u16, x: u16, color: Color) {
+        let index = self.index(y, x);
+        self.colors[index] = color;
+    }
+
+    pub fn get_depth(&self, y: u16, x: u16) -> f64 {
+        self.depths[self.index(y, x)]
+    }
+
+    pub fn set_depth(&mut self, y: u16, x: u16, depth: f64) {
+        let index = self.index(y, x);
+        self.depths[index] = depth;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_columns() {
+        let mut framebuffer = Framebuffer::new(3, 10);
+        let mut bands = framebuffer.split_columns(4);
+        assert_eq!(
+            bands.iter().map(Columns::range).collect::<Vec<_>>(),
+            [0..3, 3..6, 6..9, 9..10]
+        );
+        bands[1].set_pixel(2, 4, Color::White);
+        bands[1].set_depth(2, 4, 1.5);
+        assert_eq!(bands[1].get_depth(2, 4), 1.5);
+        assert_eq!(framebuffer.colors()[(2, 4)], Color::White);
+        assert_eq!(framebuffer.columns().range(), 0..10);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub map: bool,
+    pub assets: bool,
+}
+
+/// Watches the map file and the assets directory by periodically polling their files
+/// modification times
+pub struct HotReload {
+    map: Option<PathBuf>,
+    assets_directory: Option<PathBuf>,
+    map_modification_time: Option<SystemTime>,
+    assets_modification_times: HashMap<PathBuf, SystemTime>,
+    last_check: Instant,
+    /// Error of the last reload of the map, if it failed
+    pub map_error: Option<String>,
+    /// Error of the last reload of the assets, if it failed
+    pub assets_error: Option<String>,
+}
+
+fn get_modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Get the modification times of the files in `directory` and its subdirectories
+fn get_modification_times(
+    directory: &Path,
+    modification_times: &mut HashMap<PathBuf, SystemTime>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            get_modification_times(&entry.path(), modification_times)?;
+        } else {
+            modification_times.insert(entry.path(), metadata.modified()?);
+        }
+    }
+    Ok(())
+}
+
+impl HotReload {
+    pub fn new(map: Option<PathBuf>, assets_directory: Option<PathBuf>) -> Self {
+        let mut hot_reload = Self {
+            map,
+            assets_directory,
+            map_modification_time: None,
+            assets_modification_times: HashMap::new(),
+            last_check: Instant::now(),
+            map_error: None,
+            assets_error: None,
+        };
+        hot_reload.map_modification_time = hot_reload.get_map_modification_time();
+        hot_reload.assets_modification_times = hot_reload.get_assets_modification_times();
+        hot_reload
+    }
+
+    pub fn map(&self) -> Option<&Path> {
+        self.map.as_deref()
+    }
+
+    pub fn assets_directory(&self) -> Option<&Path> {
+        self.assets_directory.as_deref()
+    }
+
+    fn get_map_modification_time(&self) -> Option<SystemTime> {
+        self.map.as_deref().and_then(get_modification_time)
+    }
+
+    fn get_assets_modification_times(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modification_times = HashMap::new();
+        if let Some(assets_directory) = &self.assets_directory {
+            // A missing or partially written directory is seen as a change on the next check
+            let _ = get_modification_times(assets_directory, &mut modification_times);
+        }
+        modification_times
+    }
+
+    /// Get the error of the last failed reload still in effect, if any
+    pub fn error(&self) -> Option<&str> {
+        self.map_error.as_deref().or(self.assets_error.as_deref())
+    }
+
+    /// Get what changed on disk since the last check, checking at most every `CHECK_INTERVAL`
+    pub fn poll(&mut self) -> Changes {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return Changes::default();
+        }
+        self.last_check = Instant::now();
+        let map_modification_time = self.get_map_modification_time();
+        let assets_modification_times = self.get_assets_modification_times();
+        let changes = Changes {
+            map: map_modification_time != self.map_modification_time,
+            assets: assets_modification_times != self.assets_modification_times,
+        };
+        self.map_modification_time = map_modification_time;
+        self.assets_modification_times = assets_modification_times;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// Set the modification time of the file at `path` to `seconds` after the epoch, file systems
+    /// not always telling apart writes happening in a row
+    fn set_modification_time(path: &Path, seconds: u64) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_poll() {
+        let directory = env::temp_dir().join(format!("hot-reload-test-{}", process::id()));
+        let assets_directory = directory.join("assets");
+        fs::create_dir_all(assets_directory.join("sprites")).unwrap();
+        let map = directory.join("level.toml");
+        fs::write(&map, "").unwrap();
+        let sprite = assets_directory.join("sprites").join("penguin.png");
+        fs::write(&sprite, "").unwrap();
+        let mut hot_reload = HotReload::new(Some(map.clone()), Some(assets_directory.clone()));
+        let mut poll = || {
+            hot_reload.last_check -= CHECK_INTERVAL;
+            hot_reload.poll()
+        };
+        let map_change = Changes {
+            map: true,
+            assets: false,
+        };
+        let assets_change = Changes {
+            map: false,
+            assets: true,
+        };
+
+        assert_eq!(poll(), Changes::default());
+        set_modification_time(&map, 1);
+        assert_eq!(poll(), map_change);
+        set_modification_time(&sprite, 1);
+        assert_eq!(poll(), assets_change);
+        let metadata = assets_directory.join("sprites").join("penguin.toml");
+        fs::write(&metadata, "").unwrap();
+        assert_eq!(poll(), assets_change);
+        fs::remove_file(&metadata).unwrap();
+        assert_eq!(poll(), assets_change);
+        fs::remove_file(&map).unwrap();
+        assert_eq!(poll(), map_change);
+
+        // Changes are only checked every `CHECK_INTERVAL`
+        fs::write(&map, "").unwrap();
+        assert_eq!(hot_reload.poll(), Changes::default());
+        hot_reload.last_check -= CHECK_INTERVAL;
+        assert_eq!(hot_reload.poll(), map_change);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
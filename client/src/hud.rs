@@ -0,0 +1,129 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crossterm::style::Color;
+
+use crate::{framebuffer::Framebuffer, Raycasting};
+
+/// Period over which the frame rate is counted
+const FRAME_RATE_PERIOD: Duration = Duration::from_secs(1);
+
+/// The connection is shown as lost when nothing was received from the server for this long
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Heads-up display: a crosshair drawn over the frame, and a row of text about the game
+#[derive(Debug, Default)]
+pub struct Hud {
+    /// Times at which the frames of the last `FRAME_RATE_PERIOD` were drawn
+    frame_instants: VecDeque<Instant>,
+    frame_time: Duration,
+}
+
+/// Get the color with the opposite channels of `color`, so that it stands out on it
+fn invert(color: Color) -> Color {
+    match color {
+        Color::Rgb { r, g, b } => Color::Rgb {
+            r: u8::MAX - r,
+            g: u8::MAX - g,
+            b: u8::MAX - b,
+        },
+        _ => Color::White,
+    }
+}
+
+impl Hud {
+    /// Record that a frame is drawn, updating the frame rate and the frame time
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        if let Some(&last_instant) = self.frame_instants.back() {
+            self.frame_time = now - last_instant;
+        }
+        while self
+            .frame_instants
+            .front()
+            .is_some_and(|&instant| now - instant >= FRAME_RATE_PERIOD)
+        {
+            self.frame_instants.pop_front();
+        }
+        self.frame_instants.push_back(now);
+    }
+
+    /// Draw a crosshair at the center of the frame, inverting the colors under it
+    pub fn draw_crosshair(&self, framebuffer: &mut Framebuffer) {
+        let (height, width) = (framebuffer.height(), framebuffer.width());
+        if height == 0 || width == 0 {
+            return;
+        }
+        let (center_y, center_x) = (height / 2, width / 2);
+        let arm_length = (height / 20).max(1);
+        let mut columns = framebuffer.columns();
+        let horizontal = (center_x.saturating_sub(arm_length)..=center_x + arm_length)
+            .filter(|&x| x < width)
+            .map(|x| (center_y, x));
+        let vertical = (center_y.saturating_sub(arm_length)..=center_y + arm_length)
+            .filter(|&y| y < height && y != center_y)
+            .map(|y| (y, center_x));
+        for (y, x) in horizontal.chain(vertical) {
+            let color = invert(columns.get_pixel(y, x));
+            columns.set_pixel(y, x, color);
+        }
+    }
+
+    /// Get the text shown above the frame: frame rate and time, player position and heading, and
+    /// the connection status and player count in multiplayer
+    pub fn get_text(&self, raycasting: &Raycasting) -> String {
+        let position = raycasting.player.position;
+        let mut sections = vec![
+            format!(
+                "{} FPS {:.1} ms",
+                self.frame_instants.len(),
+                self.frame_time.as_secs_f64() * 1000.0
+            ),
+            format!(
+                "x {:.2} y {:.2} heading {:.0}°",
+                position.x,
+                position.y,
+                raycasting.player.angle.to_degrees()
+            ),
+        ];
+        if let Some(client) = &raycasting.client {
+            let status = if client.last_receive_instant.elapsed() < CONNECTION_TIMEOUT {
+                "connected"
+            } else {
+                "connection lost"
+            };
+            sections.push(format!("{}, {} players", status, raycasting.sprites.len()));
+        }
+        format!(" {}", sections.join(" | "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_crosshair() {
+        let mut framebuffer = Framebuffer::new(40, 60);
+        Hud::default().draw_crosshair(&mut framebuffer);
+        let colors = framebuffer.colors();
+        assert_eq!(colors[(20, 28)], Color::White);
+        assert_eq!(colors[(22, 30)], Color::White);
+        assert_eq!(colors[(20, 27)], Color::Black);
+        assert_eq!(colors[(21, 31)], Color::Black);
+        assert_eq!(
+            invert(Color::Rgb {
+                r: 0,
+                g: 100,
+                b: 255
+            }),
+            Color::Rgb {
+                r: 255,
+                g: 155,
+                b: 0
+            }
+        );
+    }
+}
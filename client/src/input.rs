@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{Event, KeyCode, MouseEventKind};
+use nalgebra::Vector2;
+use serde::{
+    de::{self, IntoDeserializer},
+    Deserialize, Deserializer,
+};
+
+use crate::window::Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    LookUp,
+    LookDown,
+    ToggleRun,
+    Jump,
+    Crouch,
+    ToggleFlashlight,
+}
+
+/// Key that can be written in the config file, eg. "w", "Left", "Esc", "Space"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key(pub KeyCode);
+
+impl Key {
+    pub fn parse(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(char), None) = (chars.next(), chars.next()) {
+            return Some(Self(KeyCode::Char(char.to_ascii_lowercase())));
+        }
+        let code = match name.to_ascii_lowercase().as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "insert" => KeyCode::Insert,
+            "delete" => KeyCode::Delete,
+            function_key => {
+                let number = function_key.strip_prefix('f')?.parse().ok()?;
+                KeyCode::F(number)
+            }
+        };
+        Some(Self(code))
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> de::Visitor<'de> for KeyVisitor {
+            type Value = Key;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a key name")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Key, E> {
+                Key::parse(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+#[derive(Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<Key>>,
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns `true` if one of the keys bound to `action` was read during the last call to
+    /// [`Window::poll_events`].
+    pub fn is_active(&self, window: &mut Window, action: Action) -> bool {
+        self.keys(action).iter().any(|key| window.get_key(key.0))
+    }
+}
+
+/// Longest time between two events of a held key, the terminal waiting a while before repeating
+/// them
+const KEY_REPEAT_DELAY: Duration = Duration::from_millis(700);
+
+/// Detects the presses of the keys bound to actions toggling a state, which must not toggle it
+/// again while the key is held and the terminal repeats its events
+#[derive(Debug, Default)]
+pub struct KeyPresses {
+    /// When an event of a key bound to each action was last read
+    last_event_instants: HashMap<Action, Instant>,
+}
+
+impl KeyPresses {
+    /// Returns `true` if one of the keys bound to `action` was pressed during the last call to
+    /// [`Window::poll_events`], and was not already held.
+    pub fn is_pressed(
+        &mut self,
+        key_bindings: &KeyBindings,
+        window: &mut Window,
+        action: Action,
+    ) -> bool {
+        let keys = key_bindings.keys(action);
+        if !keys.iter().any(|key| window.get_key_press(key.0)) {
+            return false;
+        }
+        let now = Instant::now();
+        let was_held = self
+            .last_event_instants
+            .insert(action, now)
+            .is_some_and(|instant| now - instant < KEY_REPEAT_DELAY);
+        !was_held
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = HashMap::from([
+            (Action::Quit, vec![Key(KeyCode::Esc)]),
+            (Action::MoveForward, vec![Key(KeyCode::Char('w'))]),
+            (Action::MoveBackward, vec![Key(KeyCode::Char('s'))]),
+            (Action::StrafeLeft, vec![Key(KeyCode::Char('a'))]),
+            (Action::StrafeRight, vec![Key(KeyCode::Char('d'))]),
+            (Action::TurnLeft, vec![Key(KeyCode::Left)]),
+            (Action::TurnRight, vec![Key(KeyCode::Right)]),
+            (Action::LookUp, vec![Key(KeyCode::Up)]),
+            (Action::LookDown, vec![Key(KeyCode::Down)]),
+            (Action::ToggleRun, vec![Key(KeyCode::Char('r'))]),
+            (Action::Jump, vec![Key(KeyCode::Char(' '))]),
+            (Action::Crouch, vec![Key(KeyCode::Char('c'))]),
+            (Action::ToggleFlashlight, vec![Key(KeyCode::Char('f'))]),
+        ]);
+        Self { bindings }
+    }
+}
+
+/// Actions missing from the config file keep their default keys
+impl<'de> Deserialize<'de> for KeyBindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut key_bindings = Self::default();
+        for (name, keys) in HashMap::<String, Vec<Key>>::deserialize(deserializer)? {
+            let action = Action::deserialize(name.as_str().into_deserializer())?;
+            key_bindings.bindings.insert(action, keys);
+        }
+        Ok(key_bindings)
+    }
+}
+
+/// Player input read from the events of the frames since the last simulation step, which applies
+/// it, so that it is neither lost when a frame has no step nor repeated when it has several
+#[derive(Debug, Default)]
+pub struct PlayerInput {
+    /// Movement direction of each frame in which movement keys were pressed, x being forward and
+    /// y to the left, with whether the run modifier was held
+    pub movements: Vec<(Vector2<f64>, bool)>,
+    /// Number of frames in which a turning key was pressed, positive to the left
+    pub turn: f64,
+    /// Number of frames in which a vertical look key was pressed, positive upward
+    pub look: f64,
+    /// Mouse turning angles in radians, positive to the right and upward
+    pub mouse_motion: Vector2<f64>,
+    pub jump: bool,
+    pub toggle_run: bool,
+    pub toggle_crouch: bool,
+    pub toggle_flashlight: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MouseConfig {
+    pub enabled: bool,
+    /// Turning angle in degrees per terminal cell of mouse motion
+    pub sensitivity: f64,
+    /// Only turn while a mouse button is held, so that the pointer can be moved back to the
+    /// center of the window by releasing it
+    pub drag_to_turn: bool,
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sensitivity: 3.0,
+            drag_to_turn: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MouseLook {
+    sensitivity: f64,
+    drag_to_turn: bool,
+    last_position: Option<(u16, u16)>,
+}
+
+impl MouseLook {
+    pub fn new(config: &MouseConfig) -> Self {
+        Self {
+            sensitivity: config.sensitivity.to_radians(),
+            drag_to_turn: config.drag_to_turn,
+            last_position: None,
+        }
+    }
+
+    /// Get the horizontal and vertical turning angles in radians from the mouse motion of
+    /// `events`, positive to the right and upward
+    pub fn update(&mut self, events: &[Event]) -> Vector2<f64> {
+        let mut motion = Vector2::zeros();
+        for event in events {
+            let mouse_event = match event {
+                Event::Mouse(mouse_event) => mouse_event,
+                _ => continue,
+            };
+            let position = (mouse_event.column, mouse_event.row);
+            match mouse_event.kind {
+                MouseEventKind::Moved if self.drag_to_turn => {
+                    self.last_position = None;
+                    continue;
+                }
+                MouseEventKind::Moved | MouseEventKind::Drag(_) => (),
+                MouseEventKind::Down(_) | MouseEventKind::Up(_) => {
+                    self.last_position = Some(position);
+                    continue;
+                }
+                _ => continue,
+            }
+            if let Some(last_position) = self.last_position {
+                motion.x += position.0 as f64 - last_position.0 as f64;
+                motion.y += last_position.1 as f64 - position.1 as f64;
+            }
+            self.last_position = Some(position);
+        }
+        motion * self.sensitivity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyEvent, KeyModifiers, MouseButton, MouseEvent};
+
+    use super::*;
+
+    fn mouse_event(kind: MouseEventKind, column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    #[test]
+    fn test_mouse_look() {
+        let mut mouse_look = MouseLook::new(&MouseConfig {
+            enabled: true,
+            sensitivity: 2.0,
+            drag_to_turn: false,
+        });
+        assert_eq!(
+            mouse_look.update(&[mouse_event(MouseEventKind::Moved, 10, 5)]),
+            Vector2::zeros()
+        );
+        // Right and up, the terminal rows going down
+        let motion = mouse_look.update(&[
+            mouse_event(MouseEventKind::Moved, 12, 5),
+            Event::Key(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE)),
+            mouse_event(MouseEventKind::Moved, 13, 4),
+        ]);
+        assert!((motion - Vector2::new(3.0, 1.0) * 2_f64.to_radians()).norm() < 1e-10);
+
+        let mut mouse_look = MouseLook::new(&MouseConfig {
+            enabled: true,
+            sensitivity: 1.0,
+            drag_to_turn: true,
+        });
+        let motion = mouse_look.update(&[
+            mouse_event(MouseEventKind::Moved, 10, 5),
+            mouse_event(MouseEventKind::Moved, 12, 5),
+        ]);
+        assert_eq!(motion, Vector2::zeros());
+        let motion = mouse_look.update(&[
+            mouse_event(MouseEventKind::Down(MouseButton::Left), 12, 5),
+            mouse_event(MouseEventKind::Drag(MouseButton::Left), 15, 7),
+        ]);
+        assert!((motion - Vector2::new(3.0, -2.0) * 1_f64.to_radians()).norm() < 1e-10);
+        // Moving the pointer back without a button held does not turn, even on the next drag
+        let motion = mouse_look.update(&[
+            mouse_event(MouseEventKind::Up(MouseButton::Left), 15, 7),
+            mouse_event(MouseEventKind::Moved, 10, 5),
+            mouse_event(MouseEventKind::Drag(MouseButton::Left), 11, 5),
+        ]);
+        assert_eq!(motion, Vector2::zeros());
+    }
+}
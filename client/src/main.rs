@@ -1,18 +1,51 @@
+use assets::Assets;
+use camera::Camera;
 use clap::Parser;
-use crossterm::{event::KeyCode, Result};
-use nalgebra::Point2;
-use sprite_sheet::SpriteSheet;
-use std::io::{self, Cursor};
+use color_mode::ColorMode;
+use config::Config;
+use crossterm::{event::KeyModifiers, Result};
+use framebuffer::Framebuffer;
+use hot_reload::HotReload;
+use hud::Hud;
+use input::{Action, KeyBindings, KeyPresses, MouseLook, PlayerInput};
+use nalgebra::{Point2, Vector2};
+use std::f64;
+use std::fs;
+use std::io;
+use std::mem;
 use std::net::{ToSocketAddrs, UdpSocket};
-use std::{f64, time::Instant};
-use winterm::Window;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Instant;
+use window::Window;
 
+mod assets;
+mod bench;
+mod camera;
+mod color_mode;
+mod config;
+mod framebuffer;
+mod hot_reload;
+mod hud;
+mod input;
 mod player;
+mod recording;
 mod rendering;
 mod sprite_sheet;
+mod terminal_output;
+mod window;
 mod window_sprite;
 
-use common::{get_normalized_radians_angle, sprite::Sprite};
+use common::{
+    get_normalized_radians_angle,
+    level::Level,
+    light::LightMap,
+    map::Map,
+    sprite::{Sprite, WallSprite},
+    timestep::FixedTimestep,
+    MAX_DATAGRAM_SIZE, MAX_SKIN_LENGTH,
+};
 use player::Player;
 use rendering::render;
 
@@ -20,148 +53,375 @@ use rendering::render;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Server address (eg. "127.0.0.1:4242")
+    /// Server address (eg. "127.0.0.1:4242"), overrides the config file one
     server_address: Option<String>,
+    /// Config file path, defaults to "raycasting-term/config.toml" in the user's config directory
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Level file, overrides the config file one
+    #[arg(short, long)]
+    map: Option<PathBuf>,
+    /// Assets directory, overrides the config file one
+    #[arg(short, long)]
+    assets: Option<PathBuf>,
+    /// Sprite sheet seen by the other players, overrides the config file one
+    #[arg(short, long)]
+    skin: Option<String>,
+    /// Reload the level and the assets when they change on disk
+    #[arg(long)]
+    dev: bool,
+    /// Colors used to draw, overrides the config file one and the one detected from the terminal
+    #[arg(long)]
+    color_mode: Option<ColorMode>,
+    /// Render FRAMES frames of a camera going through the level in singleplayer, without drawing
+    /// them, then print how long the rendering passes took
+    #[arg(long, value_name = "FRAMES")]
+    bench: Option<usize>,
+    /// Save what is drawn to the terminal to an asciinema file, eg. "out.cast"
+    #[arg(long, value_name = "PATH", conflicts_with = "bench")]
+    record: Option<PathBuf>,
 }
 
 pub struct Client {
     socket: UdpSocket,
     id: u32,
+    skin: String,
+    /// When something was last received from the server
+    last_receive_instant: Instant,
+    receive_buffer: Vec<u8>,
 }
 
 impl Client {
-    fn new<A: ToSocketAddrs>(addr: A, position: &Point2<f64>, angle: f64) -> io::Result<Self> {
+    fn new<A: ToSocketAddrs>(
+        addr: A,
+        position: &Point2<f64>,
+        angle: f64,
+        skin: &str,
+    ) -> io::Result<Self> {
+        if skin.len() > MAX_SKIN_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("skin name longer than {} bytes", MAX_SKIN_LENGTH),
+            ));
+        }
         let socket = UdpSocket::bind("0.0.0.0:0")?;
         socket.connect(addr)?;
-        socket.send(&bincode::serialize(&(position, angle)).unwrap())?;
+        socket.send(&bincode::serialize(&(position, angle, skin)).unwrap())?;
         // TODO: resend until id is received
-        let mut buf = [0; 128];
-        let len = socket.recv(&mut buf)?;
-        let id = bincode::deserialize(&buf[..len]).unwrap();
+        let mut receive_buffer = vec![0; MAX_DATAGRAM_SIZE];
+        let len = socket.recv(&mut receive_buffer)?;
+        let id = bincode::deserialize(&receive_buffer[..len])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         socket.set_nonblocking(true)?;
-        Ok(Self { socket, id })
+        Ok(Self {
+            socket,
+            id,
+            skin: skin.to_owned(),
+            last_receive_instant: Instant::now(),
+            receive_buffer,
+        })
     }
 }
 
 pub struct Raycasting {
     window: Window,
     client: Option<Client>,
+    map: Map,
     player: Player,
+    /// Camera of the player at the previous simulation step
+    previous_camera: Camera,
+    /// The player went through a portal during the last simulation step
+    has_teleported: bool,
+    /// Camera interpolated between the last two simulation steps, used for rendering
+    camera: Camera,
     sprites: Vec<Sprite>,
-    sprite_sheets: Vec<SpriteSheet>,
-    z_buffer: Vec<f64>,
+    wall_sprites: Vec<WallSprite>,
+    light_map: LightMap,
+    /// Name of the panoramic texture seen above the outdoor cells
+    sky: Option<String>,
+    assets: Assets,
+    /// Frame being rendered, drawn to the window once complete
+    framebuffer: Framebuffer,
+    /// Maximum number of threads rendering bands of columns of the frame in parallel
+    render_thread_count: usize,
+    key_bindings: KeyBindings,
+    key_presses: KeyPresses,
+    mouse_look: Option<MouseLook>,
+    /// Input waiting for the next simulation step
+    input: PlayerInput,
+    hot_reload: Option<HotReload>,
+    hud: Option<Hud>,
     should_stop: bool,
 }
 
 impl Raycasting {
-    fn new(server_address: Option<&str>) -> Result<Self> {
-        let height = 45;
-        let width = 80;
-        let position = Point2::new(3.0, 4.0);
-        let angle = 180.0_f64.to_radians();
-        let (client, sprites) = match server_address {
-            Some(addr) => (Some(Client::new(addr, &position, angle)?), vec![]),
-            None => (
-                None,
-                vec![
-                    Sprite::new(0, Point2::new(4.0, 6.0), 0, Some(90.0_f64.to_radians())),
-                    Sprite::new(1, Point2::new(6.9, 4.0), 0, None),
-                ],
+    /// Creates the game, drawing to a [`Window::headless`] one if `headless` is `true`.
+    fn new(config: Config, dev: bool, headless: bool) -> Result<Self> {
+        let color_mode = config.color_mode.unwrap_or_else(ColorMode::detect);
+        // Braille cells hold 4 times more pixels, the view keeping the same size in the terminal
+        let scale = if color_mode == ColorMode::Braille {
+            2
+        } else {
+            1
+        };
+        // The row of text shows the HUD, and the reload errors in dev mode
+        let has_text_row = config.hud || dev;
+        let height = config.resolution.height * scale;
+        let width = config.resolution.width * scale;
+        let level_content = match &config.map {
+            Some(path) => fs::read_to_string(path)?,
+            None => String::from(include_str!("../assets/maps/default.toml")),
+        };
+        let level = Level::parse(&level_content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let position = level.spawn_position;
+        let angle = level.spawn_angle;
+        let assets = Assets::load(config.assets_directory.as_deref())?;
+        if !assets.has_sprite_sheet(&config.skin) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("skin \"{}\" not found in the assets", config.skin),
+            ));
+        }
+        let mut player = Player::new(position, angle, config.fov.to_radians());
+        if !config.view_bob {
+            player.movement.view_bob_amplitude = 0.0;
+        }
+        let (client, sprites) = match config.server_address.as_deref() {
+            Some(addr) => (
+                Some(Client::new(addr, &position, angle, &config.skin)?),
+                vec![],
             ),
+            None => (None, level.sprites),
         };
         Ok(Self {
-            window: Window::new(height, width)?,
-            player: Player::new(position, angle, 60.0_f64.to_radians()),
+            window: if headless {
+                Window::headless(height, width, color_mode, has_text_row)
+            } else {
+                Window::new(
+                    height,
+                    width,
+                    config.mouse.enabled,
+                    color_mode,
+                    has_text_row,
+                )?
+            },
+            previous_camera: player.camera(),
+            has_teleported: false,
+            camera: player.camera(),
+            player,
             client,
+            map: level.map,
             sprites,
-            sprite_sheets: vec![SpriteSheet::new(Cursor::new(include_bytes!(
-                "../assets/penguin.png"
-            )))],
-            z_buffer: vec![0.0; width.into()],
+            wall_sprites: level.wall_sprites,
+            light_map: level.light_map,
+            sky: level.sky,
+            assets,
+            framebuffer: Framebuffer::new(height, width),
+            render_thread_count: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            key_bindings: config.key_bindings,
+            key_presses: KeyPresses::default(),
+            mouse_look: config.mouse.enabled.then(|| MouseLook::new(&config.mouse)),
+            input: PlayerInput::default(),
+            hot_reload: dev.then(|| HotReload::new(config.map, config.assets_directory)),
+            hud: config.hud.then(Hud::default),
             should_stop: false,
         })
     }
 
+    fn is_action_active(&mut self, action: Action) -> bool {
+        self.key_bindings.is_active(&mut self.window, action)
+    }
+
+    /// Returns `true` if a key bound to `action` was just pressed, and not held since before.
+    fn is_action_pressed(&mut self, action: Action) -> bool {
+        self.key_presses
+            .is_pressed(&self.key_bindings, &mut self.window, action)
+    }
+
+    /// Reload the level and the assets that changed on disk, keeping the player where it is.
+    ///
+    /// Files being edited may be invalid for a while, so errors are shown above the view and the
+    /// previous version is kept.
+    fn hot_reload(&mut self) {
+        let hot_reload = match &mut self.hot_reload {
+            Some(hot_reload) => hot_reload,
+            None => return,
+        };
+        let changes = hot_reload.poll();
+        if changes.map {
+            if let Some(path) = hot_reload.map() {
+                let level = fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| Level::parse(&content).map_err(|e| e.to_string()));
+                let error = level
+                    .as_ref()
+                    .err()
+                    .map(|e| format!("{}: {}", path.display(), e));
+                if let Ok(level) = level {
+                    self.map = level.map;
+                    self.wall_sprites = level.wall_sprites;
+                    self.light_map = level.light_map;
+                    self.sky = level.sky;
+                    if self.client.is_none() {
+                        self.sprites = level.sprites;
+                    }
+                }
+                hot_reload.map_error = error;
+            }
+        }
+        if changes.assets {
+            match Assets::load(hot_reload.assets_directory()) {
+                Ok(assets) => {
+                    self.assets = assets;
+                    hot_reload.assets_error = None;
+                }
+                Err(e) => hot_reload.assets_error = Some(e.to_string()),
+            }
+        }
+    }
+
+    /// Read the player input from the events of the frame, adding it to the one waiting for the
+    /// next simulation step
+    fn read_input(&mut self) {
+        if let Some(mouse_look) = &mut self.mouse_look {
+            self.input.mouse_motion += mouse_look.update(self.window.events());
+        }
+        let mut direction = Vector2::zeros();
+        if self.is_action_active(Action::MoveForward) {
+            direction.x += 1.0;
+        }
+        if self.is_action_active(Action::MoveBackward) {
+            direction.x -= 1.0;
+        }
+        if self.is_action_active(Action::StrafeLeft) {
+            direction.y += 1.0;
+        }
+        if self.is_action_active(Action::StrafeRight) {
+            direction.y -= 1.0;
+        }
+        if direction != Vector2::zeros() {
+            let is_run_modifier_pressed = self.window.get_modifiers(KeyModifiers::SHIFT);
+            self.input
+                .movements
+                .push((direction, is_run_modifier_pressed));
+        }
+        for (action, turn) in [(Action::TurnLeft, 1.0), (Action::TurnRight, -1.0)] {
+            if self.is_action_active(action) {
+                self.input.turn += turn;
+            }
+        }
+        for (action, look) in [(Action::LookUp, 1.0), (Action::LookDown, -1.0)] {
+            if self.is_action_active(action) {
+                self.input.look += look;
+            }
+        }
+        if self.is_action_active(Action::Jump) {
+            self.input.jump = true;
+        }
+        // Pressed twice before a step, a state is toggled back
+        self.input.toggle_run ^= self.is_action_pressed(Action::ToggleRun);
+        self.input.toggle_crouch ^= self.is_action_pressed(Action::Crouch);
+        self.input.toggle_flashlight ^= self.is_action_pressed(Action::ToggleFlashlight);
+    }
+
     fn instantaneous_update(&mut self) -> Result<()> {
-        if self.window.get_key(KeyCode::Esc) {
+        if self.is_action_active(Action::Quit) {
             self.should_stop = true;
         }
-        if let Some(client) = &self.client {
-            let mut buf = [0; 4096];
-            match client.socket.recv(&mut buf) {
+        self.read_input();
+        if let Some(client) = &mut self.client {
+            match client.socket.recv(&mut client.receive_buffer) {
                 Ok(len) => {
-                    self.sprites = bincode::deserialize(&buf[..len]).unwrap();
+                    client.last_receive_instant = Instant::now();
+                    // Invalid datagrams are ignored, the server sending the sprites again at its
+                    // next tick
+                    let sprites =
+                        bincode::deserialize::<Vec<Sprite>>(&client.receive_buffer[..len]);
+                    if let Ok(mut sprites) = sprites {
+                        for sprite in &mut sprites {
+                            if let Some(previous_sprite) = self
+                                .sprites
+                                .iter()
+                                .find(|previous| previous.id == sprite.id)
+                            {
+                                if previous_sprite.animation.name == sprite.animation.name {
+                                    sprite.animation.time = previous_sprite.animation.time;
+                                }
+                            }
+                        }
+                        self.sprites = sprites;
+                    }
                     Ok(())
                 }
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
                 Err(e) => Err(e),
             }?;
-            client
-                .socket
-                .send(&bincode::serialize(&(&self.player.position, self.player.angle)).unwrap())?;
+            client.socket.send(
+                &bincode::serialize(&(&self.player.position, self.player.angle, &client.skin))
+                    .unwrap(),
+            )?;
         }
         Ok(())
     }
 
-    fn continuous_update(&mut self, delta_time: f64) {
-        const TRANSLATION_SPEED: f64 = 30.0;
+    /// Apply the input waiting for this step to the player
+    fn apply_input(&mut self, delta_time: f64) {
         let rotation_speed: f64 = 300.0_f64.to_radians();
 
-        if self.window.get_key(KeyCode::Char('w')) {
-            self.player.translate(
-                self.player.angle.cos() * TRANSLATION_SPEED * delta_time,
-                -self.player.angle.sin() * TRANSLATION_SPEED * delta_time,
-            );
-        } else {
-        }
-        if self.window.get_key(KeyCode::Char('s')) {
-            self.player.translate(
-                -self.player.angle.cos() * TRANSLATION_SPEED * delta_time,
-                self.player.angle.sin() * TRANSLATION_SPEED * delta_time,
-            );
+        let input = mem::take(&mut self.input);
+        let player = &mut self.player;
+        player.always_run ^= input.toggle_run;
+        player.is_crouching ^= input.toggle_crouch;
+        player.is_flashlight_on ^= input.toggle_flashlight;
+        for (direction, is_run_modifier_pressed) in input.movements {
+            player.accelerate(direction, is_run_modifier_pressed);
         }
-        if self.window.get_key(KeyCode::Char('a')) {
-            self.player.translate(
-                (self.player.angle + f64::consts::FRAC_PI_2).cos() * TRANSLATION_SPEED * delta_time,
-                -(self.player.angle + f64::consts::FRAC_PI_2).sin()
-                    * TRANSLATION_SPEED
-                    * delta_time,
-            );
-        }
-        if self.window.get_key(KeyCode::Char('d')) {
-            self.player.translate(
-                -(self.player.angle + f64::consts::FRAC_PI_2).cos()
-                    * TRANSLATION_SPEED
-                    * delta_time,
-                (self.player.angle + f64::consts::FRAC_PI_2).sin() * TRANSLATION_SPEED * delta_time,
-            );
+        if input.jump {
+            player.jump();
         }
+        player.angle = get_normalized_radians_angle(
+            player.angle + input.turn * rotation_speed * delta_time - input.mouse_motion.x,
+        );
+        player.look_vertically(input.look * rotation_speed * delta_time + input.mouse_motion.y);
+    }
 
-        if self.window.get_key(KeyCode::Left) {
-            self.player.angle += rotation_speed * delta_time;
-            self.player.angle = get_normalized_radians_angle(self.player.angle);
+    fn continuous_update(&mut self, delta_time: f64) {
+        self.apply_input(delta_time);
+        self.has_teleported = self
+            .player
+            .update_position(&self.map, delta_time)
+            .teleport
+            .is_some();
+        for sprite in &mut self.sprites {
+            sprite.animation.time += delta_time;
         }
-        if self.window.get_key(KeyCode::Right) {
-            self.player.angle -= rotation_speed * delta_time;
-            self.player.angle = get_normalized_radians_angle(self.player.angle);
+        for wall_sprite in &mut self.wall_sprites {
+            wall_sprite.animation.time += delta_time;
         }
+        self.light_map.update(delta_time);
+        self.player.update_vertical_position(delta_time);
     }
 
     fn run(&mut self) -> Result<()> {
-        let start_time = Instant::now();
-        let mut consumned_seconds = 0.0;
-        let max_elapsed_time = 0.03;
+        const SIMULATION_STEP: f64 = 1.0 / 60.0;
+
+        let mut timestep = FixedTimestep::new(SIMULATION_STEP);
         while !self.should_stop {
             self.window.poll_events()?;
+            self.hot_reload();
             self.instantaneous_update()?;
-            let elapsed_time = start_time.elapsed().as_secs_f64() - consumned_seconds;
-            consumned_seconds += elapsed_time;
-            let delta_time = if elapsed_time < max_elapsed_time {
-                elapsed_time
-            } else {
-                max_elapsed_time
-            };
-            self.continuous_update(delta_time);
+            timestep.update();
+            while timestep.consume_step() {
+                self.previous_camera = self.player.camera();
+                self.continuous_update(timestep.step());
+            }
+            self.camera = Camera::interpolate(
+                &self.previous_camera,
+                &self.player.camera(),
+                timestep.alpha(),
+                self.has_teleported,
+            );
             render(self)?;
         }
         Ok(())
@@ -169,7 +429,34 @@ impl Raycasting {
 }
 
 fn raycasting(args: Args) -> Result<()> {
-    let mut raycasting = Raycasting::new(args.server_address.as_deref())?;
+    let mut config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::load_default()?,
+    };
+    if args.server_address.is_some() {
+        config.server_address = args.server_address;
+    }
+    if args.map.is_some() {
+        config.map = args.map;
+    }
+    if args.assets.is_some() {
+        config.assets_directory = args.assets;
+    }
+    if let Some(skin) = args.skin {
+        config.skin = skin;
+    }
+    if args.color_mode.is_some() {
+        config.color_mode = args.color_mode;
+    }
+    if let Some(frame_count) = args.bench {
+        config.server_address = None;
+        let mut raycasting = Raycasting::new(config, false, true)?;
+        return bench::run_bench(&mut raycasting, frame_count);
+    }
+    let mut raycasting = Raycasting::new(config, args.dev, false)?;
+    if let Some(path) = &args.record {
+        raycasting.window.start_recording(path)?;
+    }
     raycasting.run()?;
     Ok(())
 }
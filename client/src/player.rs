@@ -1,28 +1,205 @@
 use std::f64;
 
-use common::{get_normalized_radians_angle, MAP};
-use nalgebra::Point2;
+use common::map::Map;
+use nalgebra::{Point2, Vector2};
+
+use crate::camera::Camera;
+
+const STANDING_EYE_HEIGHT: f64 = 0.5;
+const CROUCHING_EYE_HEIGHT: f64 = 0.3;
+const CROUCHING_SPEED: f64 = 2.0;
+const JUMP_SPEED: f64 = 2.0;
+const GRAVITY: f64 = 9.0;
+const MAX_PITCH: f64 = 0.5;
+/// Highest floor height difference that can be walked up without jumping
+const MAX_STEP_HEIGHT: f64 = 0.3;
+
+pub struct MovementParameters {
+    /// Maximum walking speed in units per second
+    pub walking_speed: f64,
+    pub running_speed_multiplier: f64,
+    /// Speed gained per movement key event, the terminal repeating them while a key is held
+    pub acceleration: f64,
+    /// Exponential decay rate of the speed per second
+    pub friction: f64,
+    /// Vertical amplitude of the view bobbing at walking speed, 0 to disable it
+    pub view_bob_amplitude: f64,
+    /// View bobbing oscillations per unit walked
+    pub view_bob_frequency: f64,
+}
+
+impl Default for MovementParameters {
+    fn default() -> Self {
+        Self {
+            walking_speed: 3.0,
+            running_speed_multiplier: 2.0,
+            acceleration: 1.0,
+            friction: 8.0,
+            view_bob_amplitude: 0.02,
+            view_bob_frequency: 0.8,
+        }
+    }
+}
+
+/// What happened to the player during a [`Player::translate`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TranslationOutcome {
+    /// A wall stopped the motion along the x axis
+    pub is_x_blocked: bool,
+    /// A wall stopped the motion along the y axis
+    pub is_y_blocked: bool,
+    /// Offset added to the position by going through a portal
+    pub teleport: Option<Vector2<f64>>,
+}
 
 pub struct Player {
     pub position: Point2<f64>,
+    pub velocity: Vector2<f64>,
+    pub movement: MovementParameters,
+    /// Run by default, the run modifier making the player walk
+    pub always_run: bool,
     pub angle: f64,
+    /// Vertical look angle in [-MAX_PITCH; MAX_PITCH], positive when looking up
+    pub pitch: f64,
     pub horizontal_fov: f64,
+    pub is_crouching: bool,
+    pub is_flashlight_on: bool,
+    /// Height of the eyes when on the ground, lowered when crouching
+    stance_eye_height: f64,
+    /// Floor height of the cell the player is in
+    ground_height: f64,
+    /// Height of the feet above the ground
+    elevation: f64,
+    vertical_speed: f64,
+    view_bob_phase: f64,
 }
 
 impl Player {
     pub fn new(position: Point2<f64>, angle: f64, horizontal_fov: f64) -> Self {
         Self {
             position,
+            velocity: Vector2::zeros(),
+            movement: MovementParameters::default(),
+            always_run: false,
             angle,
+            pitch: 0.0,
             horizontal_fov,
+            is_crouching: false,
+            is_flashlight_on: false,
+            stance_eye_height: STANDING_EYE_HEIGHT,
+            ground_height: 0.0,
+            elevation: 0.0,
+            vertical_speed: 0.0,
+            view_bob_phase: 0.0,
+        }
+    }
+
+    /// Height of the eyes, a wall being 1 unit high
+    pub fn eye_height(&self) -> f64 {
+        let view_bob = if self.elevation == 0.0 {
+            let speed_ratio = self.velocity.norm() / self.movement.walking_speed;
+            self.movement.view_bob_amplitude * speed_ratio * self.view_bob_phase.sin()
+        } else {
+            0.0
+        };
+        self.ground_height + self.elevation + self.stance_eye_height + view_bob
+    }
+
+    /// Accelerate toward `direction`, whose x is forward and y is to the left of the player
+    pub fn accelerate(&mut self, direction: Vector2<f64>, is_run_modifier_pressed: bool) {
+        if direction == Vector2::zeros() {
+            return;
+        }
+        let direction = direction.normalize();
+        let forward = Vector2::new(self.angle.cos(), -self.angle.sin());
+        let left = Vector2::new(-forward.y, forward.x);
+        self.velocity += (forward * direction.x + left * direction.y) * self.movement.acceleration;
+        let max_speed = if self.always_run != is_run_modifier_pressed {
+            self.movement.walking_speed * self.movement.running_speed_multiplier
+        } else {
+            self.movement.walking_speed
+        };
+        self.velocity = self.velocity.cap_magnitude(max_speed);
+    }
+
+    /// Move according to the velocity, stopping against walls, and apply friction
+    pub fn update_position(&mut self, map: &Map, delta_time: f64) -> TranslationOutcome {
+        self.velocity *= (-self.movement.friction * delta_time).exp();
+        let translation = self.velocity * delta_time;
+        let previous_position = self.position;
+        let outcome = self.translate(map, translation.x, translation.y);
+        let actual_translation =
+            self.position - previous_position - outcome.teleport.unwrap_or_default();
+        if outcome.is_x_blocked {
+            self.velocity.x = 0.0;
+        }
+        if outcome.is_y_blocked {
+            self.velocity.y = 0.0;
+        }
+        self.view_bob_phase = (self.view_bob_phase
+            + actual_translation.norm() * self.movement.view_bob_frequency * f64::consts::TAU)
+            % f64::consts::TAU;
+        self.update_ground_height(map);
+        outcome
+    }
+
+    /// Step up onto or fall from the floor of the cell the player moved into
+    fn update_ground_height(&mut self, map: &Map) {
+        let ground_height = map.cell_type_at(&self.position).floor_height;
+        self.elevation = f64::max(0.0, self.elevation + self.ground_height - ground_height);
+        self.ground_height = ground_height;
+    }
+
+    /// Whether the player can stand in the cell containing `position`
+    fn can_enter(&self, map: &Map, position: &Point2<f64>) -> bool {
+        let cell_type = map.cell_type_at(position);
+        !cell_type.is_wall()
+            && cell_type.floor_height <= self.ground_height + self.elevation + MAX_STEP_HEIGHT
+    }
+
+    pub fn look_vertically(&mut self, angle: f64) {
+        self.pitch = (self.pitch + angle).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn jump(&mut self) {
+        if self.elevation == 0.0 {
+            self.vertical_speed = JUMP_SPEED;
         }
     }
 
-    pub fn translate(&mut self, x: f64, y: f64) {
+    pub fn update_vertical_position(&mut self, delta_time: f64) {
+        if self.elevation > 0.0 || self.vertical_speed > 0.0 {
+            self.vertical_speed -= GRAVITY * delta_time;
+            self.elevation += self.vertical_speed * delta_time;
+            if self.elevation <= 0.0 {
+                self.elevation = 0.0;
+                self.vertical_speed = 0.0;
+            }
+        }
+        let target_eye_height = if self.is_crouching {
+            CROUCHING_EYE_HEIGHT
+        } else {
+            STANDING_EYE_HEIGHT
+        };
+        let max_eye_height_change = CROUCHING_SPEED * delta_time;
+        self.stance_eye_height += (target_eye_height - self.stance_eye_height)
+            .clamp(-max_eye_height_change, max_eye_height_change);
+    }
+
+    /// Move by (x, y), stopping against walls and going through portals
+    pub fn translate(&mut self, map: &Map, x: f64, y: f64) -> TranslationOutcome {
         const DISPLACEMENT_FROM_WALL: f64 = 0.1;
 
+        let mut outcome = TranslationOutcome::default();
+
+        let previous_cell = (
+            self.position.x.floor() as i64,
+            self.position.y.floor() as i64,
+        );
+
         self.position.x += x;
-        if MAP[self.position.y as usize][self.position.x as usize] != 0 {
+        if !self.can_enter(map, &self.position) {
+            outcome.is_x_blocked = true;
             if x > 0.0 {
                 self.position.x = self.position.x.floor() - DISPLACEMENT_FROM_WALL;
             } else {
@@ -31,25 +208,162 @@ impl Player {
         }
 
         self.position.y += y;
-        if MAP[self.position.y as usize][self.position.x as usize] != 0 {
+        if !self.can_enter(map, &self.position) {
+            outcome.is_y_blocked = true;
             if y > 0.0 {
                 self.position.y = self.position.y.floor() - DISPLACEMENT_FROM_WALL;
             } else {
                 self.position.y = self.position.y.ceil() + DISPLACEMENT_FROM_WALL;
             }
         }
+
+        let cell = (
+            self.position.x.floor() as i64,
+            self.position.y.floor() as i64,
+        );
+        if cell != previous_cell {
+            if let Some(linked_cell) = map.get_portal(cell.0, cell.1) {
+                let teleport = Vector2::new(
+                    (linked_cell.0 - cell.0) as f64,
+                    (linked_cell.1 - cell.1) as f64,
+                );
+                self.position += teleport;
+                outcome.teleport = Some(teleport);
+            }
+        }
+        outcome
     }
 
-    /// Get angle to position in [-pi; pi[
-    pub fn get_angle_to(&self, position: &Point2<f64>) -> f64 {
-        let player_to_position = position - self.position;
-        let player_to_position_world_angle = (-player_to_position.y).atan2(player_to_position.x);
-        let angle_from_player =
-            get_normalized_radians_angle(player_to_position_world_angle - self.angle);
-        if angle_from_player >= f64::consts::PI {
-            angle_from_player - f64::consts::TAU
-        } else {
-            angle_from_player
+    pub fn camera(&self) -> Camera {
+        Camera {
+            position: self.position,
+            angle: self.angle,
+            pitch: self.pitch,
+            eye_height: self.eye_height(),
+            horizontal_fov: self.horizontal_fov,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accelerate_diagonally() {
+        let mut straight = Player::new(Point2::new(1.5, 1.5), 0.0, 1.0);
+        straight.accelerate(Vector2::new(1.0, 0.0), false);
+        let mut diagonal = Player::new(Point2::new(1.5, 1.5), 0.0, 1.0);
+        diagonal.accelerate(Vector2::new(1.0, 1.0), false);
+        assert!((straight.velocity.norm() - diagonal.velocity.norm()).abs() < 1e-10);
+
+        for _ in 0..100 {
+            straight.accelerate(Vector2::new(1.0, 0.0), true);
+        }
+        let running_speed =
+            straight.movement.walking_speed * straight.movement.running_speed_multiplier;
+        assert!((straight.velocity.norm() - running_speed).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_update_position() {
+        let map = Map::new(
+            vec![
+                vec![1, 1, 1, 1, 1, 1, 1, 1, 1],
+                vec![1, 0, 0, 0, 0, 0, 0, 0, 1],
+                vec![1, 1, 1, 1, 1, 1, 1, 1, 1],
+            ],
+            1,
+        )
+        .unwrap();
+        // Float rounding must not be taken for a collision
+        for step in 0..1000 {
+            let mut player = Player::new(Point2::new(1.05 + step as f64 * 0.006, 1.5), 0.0, 1.0);
+            player.velocity = Vector2::new(3.0, 0.0);
+            player.update_position(&map, 0.01);
+            assert_ne!(player.velocity.x, 0.0);
+        }
+
+        let mut player = Player::new(Point2::new(7.95, 1.95), 0.0, 1.0);
+        player.velocity = Vector2::new(3.0, 3.0);
+        player.update_position(&map, 0.1);
+        assert_eq!(player.velocity.x, 0.0);
+        assert_eq!(player.velocity.y, 0.0);
+        assert_eq!(
+            player.translate(&map, -0.1, 0.0),
+            TranslationOutcome::default()
+        );
+    }
+
+    #[test]
+    fn test_update_position_through_portal() {
+        let mut map = Map::new(
+            vec![
+                vec![1, 1, 1, 1, 1, 1, 1, 1, 1],
+                vec![1, 0, 0, 0, 0, 0, 0, 0, 1],
+                vec![1, 1, 1, 1, 1, 1, 1, 1, 1],
+            ],
+            1,
+        )
+        .unwrap();
+        map.link_portals((2, 1), (6, 1)).unwrap();
+        let mut player = Player::new(Point2::new(1.99, 1.5), 0.0, 1.0);
+        player.velocity = Vector2::new(3.0, 0.0);
+        let outcome = player.update_position(&map, 0.01);
+        assert_eq!(outcome.teleport, Some(Vector2::new(4.0, 0.0)));
+        assert!(6.0 < player.position.x && player.position.x < 6.03);
+        assert_ne!(player.velocity.x, 0.0);
+        // Only the distance walked makes the view bob
+        assert!(
+            player.view_bob_phase < 0.03 * player.movement.view_bob_frequency * f64::consts::TAU
+        );
+    }
+
+    #[test]
+    fn test_look_vertically() {
+        let mut player = Player::new(Point2::new(1.5, 1.5), 0.0, 1.0);
+        player.look_vertically(0.25);
+        assert_eq!(player.pitch, 0.25);
+        player.look_vertically(1.0);
+        assert_eq!(player.pitch, MAX_PITCH);
+        player.look_vertically(-2.0);
+        assert_eq!(player.pitch, -MAX_PITCH);
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut player = Player::new(Point2::new(1.5, 1.5), 0.0, 1.0);
+        player.jump();
+        assert_eq!(player.vertical_speed, JUMP_SPEED);
+        let mut max_elevation: f64 = 0.0;
+        for step in 0..100 {
+            player.update_vertical_position(0.01);
+            max_elevation = max_elevation.max(player.elevation);
+            if step == 10 {
+                // Jumping again in the air does nothing
+                let vertical_speed = player.vertical_speed;
+                player.jump();
+                assert_eq!(player.vertical_speed, vertical_speed);
+            }
+        }
+        let expected_max_elevation = JUMP_SPEED * JUMP_SPEED / (2.0 * GRAVITY);
+        assert!((max_elevation - expected_max_elevation).abs() < 0.02);
+        assert_eq!(player.elevation, 0.0);
+        assert_eq!(player.vertical_speed, 0.0);
+        assert_eq!(player.eye_height(), STANDING_EYE_HEIGHT);
+    }
+
+    #[test]
+    fn test_crouch() {
+        let mut player = Player::new(Point2::new(1.5, 1.5), 0.0, 1.0);
+        player.is_crouching = true;
+        player.update_vertical_position(0.05);
+        let expected_eye_height = STANDING_EYE_HEIGHT - CROUCHING_SPEED * 0.05;
+        assert!((player.eye_height() - expected_eye_height).abs() < 1e-10);
+        player.update_vertical_position(1.0);
+        assert_eq!(player.eye_height(), CROUCHING_EYE_HEIGHT);
+        player.is_crouching = false;
+        player.update_vertical_position(1.0);
+        assert_eq!(player.eye_height(), STANDING_EYE_HEIGHT);
+    }
+}
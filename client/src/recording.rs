@@ -0,0 +1,100 @@
+use std::{
+    env,
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use nalgebra::Vector2;
+
+/// Escape `text` to be written between the quotes of a JSON string
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if char.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", char as u32);
+            }
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+/// Terminal output saved with its timing in the
+/// [asciinema v2](https://docs.asciinema.org/manual/asciicast/v2/) format, to be replayed with
+/// `asciinema play`
+#[derive(Debug)]
+pub struct Recording {
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recording {
+    /// Creates the recording file at `path` for a terminal of `size` columns and rows.
+    pub fn create(path: &Path, size: Vector2<u16>) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let term = env::var("TERM").unwrap_or_default();
+        writeln!(
+            file,
+            r#"{{"version": 2, "width": {}, "height": {}, "timestamp": {}, "env": {{"TERM": "{}"}}}}"#,
+            size.x,
+            size.y,
+            timestamp,
+            escape_json(&term)
+        )?;
+        Ok(Self {
+            file,
+            start: Instant::now(),
+        })
+    }
+
+    /// Writes an event of type `code` happening now, flushing it so that the recording is
+    /// usable even if the client does not exit cleanly
+    fn write_event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        writeln!(
+            self.file,
+            r#"[{:.6}, "{}", "{}"]"#,
+            self.start.elapsed().as_secs_f64(),
+            code,
+            escape_json(data)
+        )?;
+        self.file.flush()
+    }
+
+    /// Records `bytes` written to the terminal now.
+    pub fn write_output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.write_event("o", &String::from_utf8_lossy(bytes))
+    }
+
+    /// Records that the terminal was resized to `size` columns and rows now.
+    pub fn write_resize(&mut self, size: Vector2<u16>) -> io::Result<()> {
+        self.write_event("r", &format!("{}x{}", size.x, size.y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_json() {
+        assert_eq!(
+            escape_json("\u{1b}[31m\"a\\b\"\n▀"),
+            "\\u001b[31m\\\"a\\\\b\\\"\\n▀"
+        );
+    }
+}
@@ -1,206 +1,1130 @@
-use std::{cmp, f64};
+use std::{
+    f64, mem,
+    ops::Range,
+    thread,
+    time::{Duration, Instant},
+};
 
-use common::{get_normalized_radians_angle, MAP};
+use common::{
+    get_normalized_radians_angle,
+    sprite::{VerticalAnchor, WallSprite},
+};
 use crossterm::{style::Color, Result};
-use image::GenericImageView;
-use nalgebra::Vector2;
+use image::Rgba;
+use nalgebra::{Point2, Vector2};
 
 use crate::{
+    framebuffer::{Columns, Framebuffer},
+    hot_reload::HotReload,
+    sprite_sheet::SpriteSheet,
     window_sprite::{get_sorted_window_sprites, WindowSprite},
     Raycasting,
 };
 
-fn render_column(raycasting: &mut Raycasting, x: u16, ray_angle: f64) -> Result<()> {
+/// Vertical projection of world heights on the window, shared by walls and sprites
+struct Projection {
+    /// Window row of the horizon, shifted by the camera pitch
+    horizon: f64,
+    eye_height: f64,
+    scale: f64,
+    window_height: u16,
+}
+
+impl Projection {
+    fn new(raycasting: &Raycasting) -> Self {
+        let scale = raycasting.window.height() as f64;
+        Self {
+            horizon: scale / 2.0 + raycasting.camera.pitch.tan() * scale,
+            eye_height: raycasting.camera.eye_height,
+            scale,
+            window_height: raycasting.window.height(),
+        }
+    }
+
+    /// Get the window row of a point at world height `z` seen at `distance`
+    fn y(&self, z: f64, distance: f64) -> f64 {
+        self.horizon - (z - self.eye_height) * self.scale / distance
+    }
+
+    /// Get the window row boundary, clamped to the window, of a point at world height `z` seen at
+    /// `distance`
+    fn row(&self, z: f64, distance: f64) -> u16 {
+        self.y(z, distance)
+            .round()
+            .clamp(0.0, self.window_height as f64) as u16
+    }
+
+    /// Get the window rows, clamped to the window, of the world heights from `bottom_z` to `top_z`
+    /// seen at `distance`
+    fn rows(&self, bottom_z: f64, top_z: f64, distance: f64) -> (u16, u16) {
+        (self.row(top_z, distance), self.row(bottom_z, distance))
+    }
+
+    /// Get the distance at which a horizontal surface at world height `z` is seen on the window
+    /// row `y`
+    fn distance(&self, z: f64, y: u16) -> f64 {
+        (z - self.eye_height) * self.scale / (self.horizon - (y as f64 + 0.5))
+    }
+}
+
+/// Rays that do not hit a wall within this distance are considered to go into the void
+const MAX_RAY_DISTANCE: f64 = 64.0;
+
+const CEILING_HEIGHT: f64 = 1.0;
+
+/// Light added by the flashlight at the player position, in the middle of its cone
+const FLASHLIGHT_INTENSITY: f64 = 1.5;
+/// Distance at which the flashlight fades out
+const FLASHLIGHT_RANGE: f64 = 8.0;
+/// Half of the horizontal angle of the flashlight cone
+const FLASHLIGHT_HALF_ANGLE: f64 = 0.35;
+
+/// Shade of the walls facing the x axis, the other walls and the tops being darker
+const VERTICAL_WALL_SHADE: f64 = 1.0;
+const HORIZONTAL_WALL_SHADE: f64 = 0.8;
+const WALL_TOP_SHADE: f64 = 0.6;
+
+const CEILING_COLOR: Color = Color::Rgb {
+    r: 0x64,
+    g: 0x64,
+    b: 0x64,
+};
+const FLOOR_COLOR: Color = Color::Rgb {
+    r: 0xBA,
+    g: 0x92,
+    b: 0x6C,
+};
+/// Colors of the sky without texture at the horizon and at the zenith
+const HORIZON_SKY_COLOR: [u8; 3] = [0xA0, 0xC8, 0xF0];
+const ZENITH_SKY_COLOR: [u8; 3] = [0x28, 0x50, 0xA0];
+
+fn get_wall_color(value: u8, shade: f64) -> Color {
+    let [r, g, b]: [u8; 3] = match value {
+        1 => [0xa0, 0x00, 0x00],
+        2 => [0x00, 0x00, 0xa0],
+        3 => [0x00, 0xa0, 0x00],
+        4 => [0xa0, 0xa0, 0x00],
+        _ => [0xa0, 0xa0, 0xa0],
+    };
+    let shade = |channel: u8| (channel as f64 * shade).round() as u8;
+    Color::Rgb {
+        r: shade(r),
+        g: shade(g),
+        b: shade(b),
+    }
+}
+
+/// Mirrors and portals a ray can go through before they are drawn as walls
+const MAX_RAY_BOUNCES: u32 = 8;
+
+/// Fewest columns rendered by a thread, narrower bands costing more to spawn than to render
+const MIN_BAND_WIDTH: u16 = 32;
+
+/// Transformation from a position along the straight ray of a column to the real map position,
+/// changed by the mirrors and portals the ray goes through
+#[derive(Debug, Clone, Copy)]
+struct RayTransform {
+    /// Multiplier of each coordinate, -1 once mirrored along that axis
+    flip: Vector2<f64>,
+    offset: Vector2<f64>,
+}
+
+impl RayTransform {
+    const IDENTITY: Self = Self {
+        flip: Vector2::new(1.0, 1.0),
+        offset: Vector2::new(0.0, 0.0),
+    };
+
+    fn apply(&self, position: &Point2<f64>) -> Point2<f64> {
+        Point2::from(self.flip.component_mul(&position.coords) + self.offset)
+    }
+
+    /// Get the real map coordinates of the cell at `map_coordinates` along the straight ray
+    fn apply_to_cell(&self, map_coordinates: &Vector2<i64>) -> Vector2<i64> {
+        let center = Point2::new(
+            map_coordinates.x as f64 + 0.5,
+            map_coordinates.y as f64 + 0.5,
+        );
+        let position = self.apply(&center);
+        Vector2::new(position.x.floor() as i64, position.y.floor() as i64)
+    }
+
+    /// Reflect what comes next along the straight ray through the line where its `axis`
+    /// coordinate is `coordinate`
+    fn mirror(&self, axis: usize, coordinate: f64) -> Self {
+        let mut transform = *self;
+        transform.flip[axis] = -self.flip[axis];
+        transform.offset[axis] += 2.0 * self.flip[axis] * coordinate;
+        transform
+    }
+
+    fn translate(&self, translation: Vector2<f64>) -> Self {
+        let mut transform = *self;
+        transform.offset += translation;
+        transform
+    }
+}
+
+/// Transforms of the straight ray of a column, with the euclidian distances from which they apply
+type RayTransforms = Vec<(f64, RayTransform)>;
+
+fn get_ray_transform(
+    ray_transforms: &[(f64, RayTransform)],
+    euclidian_distance: f64,
+) -> &RayTransform {
+    ray_transforms
+        .iter()
+        .rev()
+        .find(|(start_distance, _)| *start_distance <= euclidian_distance)
+        .map_or(&RayTransform::IDENTITY, |(_, transform)| transform)
+}
+
+/// Fill the `rows` of the column `x` with `color`, the depth of each row being given by `depth`
+fn fill_column(
+    columns: &mut Columns,
+    x: u16,
+    rows: Range<u16>,
+    color: Color,
+    depth: impl Fn(u16) -> f64,
+) {
+    for y in rows {
+        columns.set_depth(y, x, depth(y));
+        columns.set_pixel(y, x, color);
+    }
+}
+
+/// Render the walls, floor and ceiling of the column `x`, returning the transforms of its ray.
+///
+/// The ray goes through the map cells from the nearest to the farthest, drawing the top of each
+/// cell it leaves and the side of each higher cell it enters. Each surface is drawn above the
+/// nearer ones, so the ray continues past shorter walls until nothing farther can be seen.
+///
+/// The ray goes straight on, mirrors and portals changing which map cells it goes through instead.
+fn render_column(
+    raycasting: &Raycasting,
+    projection: &Projection,
+    columns: &mut Columns,
+    x: u16,
+    ray_angle: f64,
+) -> RayTransforms {
     let ray_direction = Vector2::new(ray_angle.cos(), ray_angle.sin());
     let mut map_coordinates = Vector2::new(
-        raycasting.player.position.x as usize,
-        raycasting.player.position.y as usize,
+        raycasting.camera.position.x.floor() as i64,
+        raycasting.camera.position.y.floor() as i64,
     );
     let mut map_coordinates_steps = Vector2::zeros();
     let mut distances = Vector2::zeros();
     if f64::consts::FRAC_PI_2 < ray_angle && ray_angle < 3.0 * f64::consts::FRAC_PI_2 {
         map_coordinates_steps.x = -1;
-        distances.x = raycasting.player.position.x.fract() / (ray_angle - f64::consts::PI).cos();
+        distances.x = raycasting.camera.position.x.fract() / (ray_angle - f64::consts::PI).cos();
     } else {
         map_coordinates_steps.x = 1;
-        distances.x = (1.0 - raycasting.player.position.x.fract()) / ray_angle.cos();
+        distances.x = (1.0 - raycasting.camera.position.x.fract()) / ray_angle.cos();
     }
     if 0.0 < ray_angle && ray_angle < f64::consts::PI {
         map_coordinates_steps.y = -1;
         distances.y =
-            raycasting.player.position.y.fract() / (ray_angle - f64::consts::FRAC_PI_2).cos();
+            raycasting.camera.position.y.fract() / (ray_angle - f64::consts::FRAC_PI_2).cos();
     } else {
         map_coordinates_steps.y = 1;
-        distances.y = (1_f64 - raycasting.player.position.y.fract())
+        distances.y = (1_f64 - raycasting.camera.position.y.fract())
             / (ray_angle + f64::consts::FRAC_PI_2).cos();
     }
     let steps = Vector2::new(
         1.0_f64.hypot(ray_direction.y / ray_direction.x),
         1.0_f64.hypot(ray_direction.x / ray_direction.y),
     );
-    let (euclidian_distance, is_vertical) = loop {
-        if distances.x < distances.y {
-            map_coordinates.x = (map_coordinates.x as i32 + map_coordinates_steps.x) as usize;
-            if MAP[map_coordinates.y][map_coordinates.x] != 0 {
-                break (distances.x, true);
-            }
+    let fisheye_correction = (raycasting.camera.angle - ray_angle).cos();
+    let max_top = raycasting.map.max_top();
+    // Rows from this one to the bottom of the window are already drawn
+    let mut free_bottom = raycasting.window.height();
+    let mut value = raycasting.map.get(map_coordinates.x, map_coordinates.y);
+    let mut cell_type = raycasting.map.get_cell_type(value);
+    let mut ray_transforms = vec![(0.0, RayTransform::IDENTITY)];
+    let mut ray_transform = RayTransform::IDENTITY;
+    loop {
+        let (euclidian_distance, is_vertical) = if distances.x < distances.y {
+            map_coordinates.x += map_coordinates_steps.x;
             distances.x += steps.x;
+            (distances.x - steps.x, true)
         } else {
-            map_coordinates.y = (map_coordinates.y as i32 + map_coordinates_steps.y) as usize;
-            if MAP[map_coordinates.y][map_coordinates.x] != 0 {
-                break (distances.y, false);
-            }
+            map_coordinates.y += map_coordinates_steps.y;
             distances.y += steps.y;
+            (distances.y - steps.y, false)
+        };
+        if euclidian_distance > MAX_RAY_DISTANCE {
+            break;
         }
-    };
-    let distance = euclidian_distance * (raycasting.player.angle - ray_angle).cos();
-    raycasting.z_buffer[x as usize] = distance;
-    let color = match MAP[map_coordinates.y][map_coordinates.x] {
-        1 => {
-            if is_vertical {
-                Color::Rgb {
-                    r: 0xa0,
-                    g: 0x00,
-                    b: 0x00,
-                }
-            } else {
-                Color::Rgb {
-                    r: 0x80,
-                    g: 0x00,
-                    b: 0x00,
-                }
+        let distance = euclidian_distance * fisheye_correction;
+        // Top of the cell the ray leaves, only visible from above
+        let top = cell_type.top();
+        if top < projection.eye_height {
+            let start = projection.row(top, distance);
+            if start < free_bottom {
+                let color = if cell_type.is_wall() {
+                    get_wall_color(value, WALL_TOP_SHADE)
+                } else {
+                    FLOOR_COLOR
+                };
+                fill_column(columns, x, start..free_bottom, color, |y| {
+                    projection.distance(top, y)
+                });
+                free_bottom = start;
             }
         }
-        2 => {
-            if is_vertical {
-                Color::Rgb {
-                    r: 0x00,
-                    g: 0x00,
-                    b: 0xa0,
-                }
-            } else {
-                Color::Rgb {
-                    r: 0x00,
-                    g: 0x00,
-                    b: 0x80,
-                }
+        let mut real_map_coordinates = ray_transform.apply_to_cell(&map_coordinates);
+        value = raycasting
+            .map
+            .get(real_map_coordinates.x, real_map_coordinates.y);
+        cell_type = raycasting.map.get_cell_type(value);
+        if ray_transforms.len() <= MAX_RAY_BOUNCES as usize {
+            let portal = raycasting
+                .map
+                .get_portal(real_map_coordinates.x, real_map_coordinates.y);
+            if cell_type.is_mirror {
+                // Reflect on the side of the cell, going back into the cell the ray left
+                let axis = if is_vertical { 0 } else { 1 };
+                let side = if map_coordinates_steps[axis] > 0 {
+                    map_coordinates[axis]
+                } else {
+                    map_coordinates[axis] + 1
+                };
+                ray_transform = ray_transform.mirror(axis, side as f64);
+            } else if let Some((linked_x, linked_y)) = portal {
+                let translation = Vector2::new(
+                    linked_x - real_map_coordinates.x,
+                    linked_y - real_map_coordinates.y,
+                );
+                ray_transform = ray_transform.translate(translation.cast());
             }
+            if cell_type.is_mirror || portal.is_some() {
+                ray_transforms.push((euclidian_distance, ray_transform));
+                real_map_coordinates = ray_transform.apply_to_cell(&map_coordinates);
+                value = raycasting
+                    .map
+                    .get(real_map_coordinates.x, real_map_coordinates.y);
+                cell_type = raycasting.map.get_cell_type(value);
+            }
+        }
+        // Side of the cell the ray enters, if it is higher
+        if cell_type.top() > top {
+            let start = projection.row(cell_type.top(), distance);
+            let end = u16::min(projection.row(top, distance), free_bottom);
+            if start < end {
+                let shade = if is_vertical {
+                    VERTICAL_WALL_SHADE
+                } else {
+                    HORIZONTAL_WALL_SHADE
+                };
+                fill_column(columns, x, start..end, get_wall_color(value, shade), |_| {
+                    distance
+                });
+                free_bottom = u16::min(free_bottom, start);
+            }
+        }
+        // The whole column is drawn
+        if free_bottom == 0 {
+            break;
+        }
+        // Farther cells are seen closer to the horizon, only the ones higher than the eyes rising
+        // above it
+        let highest_visible_row = if max_top > projection.eye_height {
+            projection.y(max_top, distance)
+        } else {
+            projection.horizon
+        };
+        if highest_visible_row >= free_bottom as f64 {
+            break;
         }
-        _ => Color::Black,
+    }
+    // Top of the last cell, up to the horizon if the ray went into the void
+    if cell_type.top() < projection.eye_height {
+        let start = projection.row(cell_type.top(), f64::INFINITY);
+        if start < free_bottom {
+            fill_column(columns, x, start..free_bottom, FLOOR_COLOR, |y| {
+                projection.distance(cell_type.top(), y)
+            });
+            free_bottom = start;
+        }
+    }
+    // Ceiling, or sky above the outdoor cells, sprites always being below it
+    for y in 0..free_bottom {
+        let (position, _) =
+            get_ceiling_position(raycasting, projection, &ray_transforms, ray_angle, y);
+        let color = if raycasting.map.cell_type_at(&position).is_outdoor {
+            get_sky_color(raycasting, projection, ray_angle, y)
+        } else {
+            CEILING_COLOR
+        };
+        columns.set_depth(y, x, f64::INFINITY);
+        columns.set_pixel(y, x, color);
+    }
+    ray_transforms
+}
+
+/// Get the real position of the ceiling seen on the row `y` of the ray going at `ray_angle`, and
+/// its euclidian distance, the ceiling being seen at `MAX_RAY_DISTANCE` if it is not above the row
+fn get_ceiling_position(
+    raycasting: &Raycasting,
+    projection: &Projection,
+    ray_transforms: &[(f64, RayTransform)],
+    ray_angle: f64,
+    y: u16,
+) -> (Point2<f64>, f64) {
+    let distance = projection.distance(CEILING_HEIGHT, y);
+    let euclidian_distance = if distance > 0.0 {
+        f64::min(
+            distance / (ray_angle - raycasting.camera.angle).cos(),
+            MAX_RAY_DISTANCE,
+        )
+    } else {
+        MAX_RAY_DISTANCE
     };
-    let height = (raycasting.window.height() as f64 / distance).round() as u16;
-    let wall_start = cmp::max(
-        0,
-        ((raycasting.window.height() as i32 - height as i32) as f32 / 2_f32).round() as u16,
-    );
-    let wall_end = cmp::min(
-        raycasting.window.height(),
-        ((raycasting.window.height() as i32 + height as i32) as f32 / 2_f32).round() as u16,
-    );
-    for y in 0..wall_start {
-        raycasting.window.set_pixel(
-            y,
-            x,
-            Color::Rgb {
-                r: 0x64,
-                g: 0x64,
-                b: 0x64,
-            },
-        );
+    // The map y axis goes down
+    let ray_direction = Vector2::new(ray_angle.cos(), -ray_angle.sin());
+    let position = raycasting.camera.position + ray_direction * euclidian_distance;
+    (
+        get_ray_transform(ray_transforms, euclidian_distance).apply(&position),
+        euclidian_distance,
+    )
+}
+
+/// Get the color of the sky seen on the row `y` of the ray going at `ray_angle`, the sky texture
+/// going around the horizon once and up to the zenith
+fn get_sky_color(
+    raycasting: &Raycasting,
+    projection: &Projection,
+    ray_angle: f64,
+    y: u16,
+) -> Color {
+    let elevation = ((projection.horizon - (y as f64 + 0.5)) / projection.scale).atan();
+    // From 0 at the horizon to 1 at the zenith
+    let height = (elevation / f64::consts::FRAC_PI_2).clamp(0.0, 1.0);
+    let texture = raycasting
+        .sky
+        .as_deref()
+        .and_then(|name| raycasting.assets.texture(name));
+    let color = match texture {
+        Some(texture) => {
+            // Turning left scrolls the sky to the right
+            let image_x = ((1.0 - get_normalized_radians_angle(ray_angle) / f64::consts::TAU)
+                * texture.width() as f64) as u32
+                % texture.width();
+            let image_y = u32::min(
+                ((1.0 - height) * texture.height() as f64) as u32,
+                texture.height() - 1,
+            );
+            let pixel = texture.get_pixel(image_x, image_y);
+            [pixel[0], pixel[1], pixel[2]]
+        }
+        None => {
+            let channel = |i: usize| {
+                (HORIZON_SKY_COLOR[i] as f64
+                    + (ZENITH_SKY_COLOR[i] as f64 - HORIZON_SKY_COLOR[i] as f64) * height)
+                    .round() as u8
+            };
+            [channel(0), channel(1), channel(2)]
+        }
+    };
+    Color::Rgb {
+        r: color[0],
+        g: color[1],
+        b: color[2],
     }
-    for y in wall_start..wall_end {
-        raycasting.window.set_pixel(y, x, color);
+}
+
+/// Blend `color` with an `alpha` from 0 to 1 over the `background` color
+fn blend(background: Color, color: [u8; 3], alpha: f64) -> Color {
+    // Pixels are only ever set to RGB colors, apart from the initial black
+    let background = match background {
+        Color::Rgb { r, g, b } => [r, g, b],
+        _ => [0, 0, 0],
+    };
+    let channel = |i: usize| {
+        (background[i] as f64 + (color[i] as f64 - background[i] as f64) * alpha).round() as u8
+    };
+    Color::Rgb {
+        r: channel(0),
+        g: channel(1),
+        b: channel(2),
     }
-    for y in wall_end..raycasting.window.height() {
-        raycasting.window.set_pixel(
-            y,
-            x,
-            Color::Rgb {
-                r: 0xBA,
-                g: 0x92,
-                b: 0x6C,
-            },
+}
+
+/// Draw a sprite pixel seen at `distance`, unless something nearer is already drawn there
+fn draw_sprite_pixel(
+    columns: &mut Columns,
+    y: u16,
+    x: u16,
+    distance: f64,
+    color: &Rgba<u8>,
+    opacity: f64,
+) {
+    if distance > columns.get_depth(y, x) {
+        return;
+    }
+    let alpha = color[3] as f64 / u8::MAX as f64 * opacity;
+    if alpha <= 0.0 {
+        return;
+    }
+    let terminal_color = if alpha >= 1.0 {
+        // Only opaque pixels hide what is behind them
+        columns.set_depth(y, x, distance);
+        Color::Rgb {
+            r: color[0],
+            g: color[1],
+            b: color[2],
+        }
+    } else {
+        blend(
+            columns.get_pixel(y, x),
+            [color[0], color[1], color[2]],
+            alpha,
+        )
+    };
+    columns.set_pixel(y, x, terminal_color);
+}
+
+/// Get the distance along the ray from `origin` in `direction` to the segment from `start` to
+/// `end`, and the position of the intersection on the segment from 0 to 1, if they intersect
+fn intersect_segment(
+    origin: &Point2<f64>,
+    direction: &Vector2<f64>,
+    start: &Point2<f64>,
+    end: &Point2<f64>,
+) -> Option<(f64, f64)> {
+    let cross = |a: &Vector2<f64>, b: &Vector2<f64>| a.x * b.y - a.y * b.x;
+    let segment = end - start;
+    let denominator = cross(direction, &segment);
+    if denominator.abs() < 1e-10 {
+        return None;
+    }
+    let origin_to_start = start - origin;
+    let distance = cross(&origin_to_start, &segment) / denominator;
+    let position = cross(&origin_to_start, direction) / denominator;
+    (distance > 0.0 && (0.0..=1.0).contains(&position)).then_some((distance, position))
+}
+
+/// Wall sprite crossed by the ray of a column
+struct WallSpriteHit<'a> {
+    distance: f64,
+    /// Position of the intersection on the wall sprite, from 0 at its start to 1 at its end
+    position: f64,
+    wall_sprite: &'a WallSprite,
+}
+
+/// Get the wall sprites crossed by the ray of angle `ray_angle` up to `max_distance`, from the
+/// nearest to the farthest
+fn get_wall_sprite_hits<'a>(
+    raycasting: &'a Raycasting,
+    ray_angle: f64,
+    max_distance: f64,
+) -> Vec<WallSpriteHit<'a>> {
+    // The map y axis goes down
+    let ray_direction = Vector2::new(ray_angle.cos(), -ray_angle.sin());
+    let mut hits: Vec<_> = raycasting
+        .wall_sprites
+        .iter()
+        .filter_map(|wall_sprite| {
+            intersect_segment(
+                &raycasting.camera.position,
+                &ray_direction,
+                &wall_sprite.start,
+                &wall_sprite.end,
+            )
+            .map(|(euclidian_distance, position)| WallSpriteHit {
+                distance: euclidian_distance * (raycasting.camera.angle - ray_angle).cos(),
+                position,
+                wall_sprite,
+            })
+        })
+        .filter(|hit| hit.distance <= max_distance)
+        .collect();
+    hits.sort_unstable_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+    hits
+}
+
+/// Render the wall sprites of `hits` on the column `x` that are farther than `distance`, from the
+/// farthest to the nearest, removing them from `hits`
+fn render_wall_sprites_column(
+    raycasting: &Raycasting,
+    projection: &Projection,
+    columns: &mut Columns,
+    x: u16,
+    hits: &mut Vec<WallSpriteHit>,
+    distance: f64,
+) {
+    while let Some(hit) = hits.last().filter(|hit| hit.distance > distance) {
+        let wall_sprite = hit.wall_sprite;
+        let sprite_sheet = raycasting.assets.sprite_sheet(&wall_sprite.sprite_sheet);
+        let frame_size = sprite_sheet.frame_size();
+        let frame_offset = sprite_sheet.get_frame_offset(&wall_sprite.animation, None);
+        let top_z = wall_sprite.bottom + wall_sprite.height;
+        let top_y = projection.y(top_z, hit.distance);
+        let image_step =
+            frame_size.y as f64 / (projection.y(wall_sprite.bottom, hit.distance) - top_y);
+        let image_x = u32::min(
+            (hit.position * frame_size.x as f64) as u32,
+            frame_size.x - 1,
         );
+        let (start_y, end_y) = projection.rows(wall_sprite.bottom, top_z, hit.distance);
+        let mut image_y = f64::max(0.0, start_y as f64 - top_y.round()) * image_step;
+        for y in start_y..end_y {
+            let color = sprite_sheet.image().get_pixel(
+                image_x + frame_offset.x,
+                u32::min(image_y as u32, frame_size.y - 1) + frame_offset.y,
+            );
+            draw_sprite_pixel(columns, y, x, hit.distance, color, wall_sprite.opacity);
+            image_y += image_step;
+        }
+        hits.pop();
     }
-    Ok(())
 }
 
-fn render_window_sprite(window_sprite: &WindowSprite, raycasting: &mut Raycasting) -> Result<()> {
-    let sprite_sheet = &raycasting.sprite_sheets[window_sprite.sprite_sheet_index];
-    let height = (raycasting.window.height() as f64 / window_sprite.distance).round() as u16;
-    let start_y = cmp::max(
-        0,
-        ((raycasting.window.height() as i32 - height as i32) as f32 / 2.0).round() as u16,
-    );
-    let end_y = cmp::min(
-        raycasting.window.height(),
-        ((raycasting.window.height() + height) as f32 / 2.0).round() as u16,
-    );
-    let image_y_step = sprite_sheet.size() as f64 / height as f64;
-    let start_image_y = f64::max(
-        0.0,
-        -((raycasting.window.height() as i32 - height as i32) as f64 / 2_f64).round()
-            * image_y_step,
-    );
-    let width =
-        (height as f32 * sprite_sheet.size() as f32 / sprite_sheet.size() as f32).round() as u16;
-    let start_x = cmp::max(
-        0,
-        (window_sprite.x as f32 - (width as f32 / 2.0) + 0.1).round() as u16,
+/// Get the world heights of the bottom and the top of `window_sprite`, its anchor being placed on
+/// the floor or the ceiling
+fn get_window_sprite_heights(
+    window_sprite: &WindowSprite,
+    sprite_sheet: &SpriteSheet,
+) -> (f64, f64) {
+    let placement = &window_sprite.placement;
+    let vertical_anchor = placement.vertical_anchor.unwrap_or(VerticalAnchor::Floor);
+    let anchor = sprite_sheet.get_anchor(vertical_anchor);
+    let world_size = sprite_sheet.get_world_size(placement);
+    let vertical_offset = placement.vertical_offset.unwrap_or(0.0);
+    let anchor_z = match vertical_anchor {
+        VerticalAnchor::Floor => window_sprite.floor_height + vertical_offset,
+        VerticalAnchor::Ceiling => CEILING_HEIGHT - vertical_offset,
+    };
+    let top_z = anchor_z + world_size.y * anchor.y as f64 / sprite_sheet.frame_size().y as f64;
+    (top_z - world_size.y, top_z)
+}
+
+/// Render a sprite on the columns whose maximum sprite distance in `max_distances` it is within,
+/// after the wall sprites of `wall_sprite_hits` behind it
+fn render_window_sprite(
+    window_sprite: &WindowSprite,
+    raycasting: &Raycasting,
+    projection: &Projection,
+    columns: &mut Columns,
+    max_distances: &[f64],
+    wall_sprite_hits: &mut [Vec<WallSpriteHit>],
+) {
+    let sprite_sheet = raycasting.assets.sprite_sheet(&window_sprite.sprite_sheet);
+    let frame_size = sprite_sheet.frame_size();
+    let anchor = sprite_sheet.get_anchor(
+        window_sprite
+            .placement
+            .vertical_anchor
+            .unwrap_or(VerticalAnchor::Floor),
     );
-    let end_x = Ord::clamp(
-        (window_sprite.x as f32 + (width as f32 / 2.0)).round() as i16,
-        0,
-        raycasting.window.width() as i16,
-    ) as u16;
-    let image_x_step = sprite_sheet.size() as f64 / width as f64;
-    let mut image_x = f64::max(
-        0.0,
-        -(window_sprite.x as f64 - (width as f64 / 2_f64) + 0.1).round() * image_x_step,
+    let world_size = sprite_sheet.get_world_size(&window_sprite.placement);
+    let (bottom_z, top_z) = get_window_sprite_heights(window_sprite, sprite_sheet);
+    let top_y = projection.y(top_z, window_sprite.distance);
+    let scale = Vector2::new(
+        world_size.x * projection.scale / window_sprite.distance / frame_size.x as f64,
+        (projection.y(bottom_z, window_sprite.distance) - top_y) / frame_size.y as f64,
     );
+    let left_x = window_sprite.x as f64 - anchor.x as f64 * scale.x;
+    let (start_y, end_y) = projection.rows(bottom_z, top_z, window_sprite.distance);
+    let window_width = raycasting.window.width() as f64;
+    let start_x = left_x.round().clamp(0.0, window_width) as u16;
+    let end_x = (left_x + frame_size.x as f64 * scale.x)
+        .round()
+        .clamp(0.0, window_width) as u16;
+    let image_step = Vector2::new(1.0 / scale.x, 1.0 / scale.y);
+    let start_image_y = f64::max(0.0, start_y as f64 - top_y.round()) * image_step.y;
+    let mut image_x = f64::max(0.0, start_x as f64 - left_x.round()) * image_step.x;
     for x in start_x..end_x {
-        if window_sprite.distance > raycasting.z_buffer[x as usize] {
-            image_x += image_x_step;
+        if window_sprite.distance > max_distances[x as usize] {
+            image_x += image_step.x;
             continue;
-        } else {
-            raycasting.z_buffer[x as usize] = window_sprite.distance;
         }
+        render_wall_sprites_column(
+            raycasting,
+            projection,
+            columns,
+            x,
+            &mut wall_sprite_hits[x as usize],
+            window_sprite.distance,
+        );
         let mut image_y = start_image_y;
         for y in start_y..end_y {
-            let color = &sprite_sheet
-                .image()
-                .get_pixel(image_x as u32, image_y as u32 + window_sprite.sprite_sheet_y_offset);
-            if color[3] != u8::MAX {
-                image_y += image_y_step;
-                continue;
+            let color = sprite_sheet.image().get_pixel(
+                u32::min(image_x as u32, frame_size.x - 1) + window_sprite.sprite_sheet_offset.x,
+                u32::min(image_y as u32, frame_size.y - 1) + window_sprite.sprite_sheet_offset.y,
+            );
+            draw_sprite_pixel(
+                columns,
+                y,
+                x,
+                window_sprite.distance,
+                color,
+                window_sprite.opacity,
+            );
+            image_y += image_step.y;
+        }
+        image_x += image_step.x;
+    }
+}
+
+/// Multiply the channels of `color` by `light`
+fn shade(color: Color, light: f64) -> Color {
+    match color {
+        Color::Rgb { r, g, b } => {
+            let shade = |channel: u8| (channel as f64 * light).round().min(u8::MAX as f64) as u8;
+            Color::Rgb {
+                r: shade(r),
+                g: shade(g),
+                b: shade(b),
             }
-            let terminal_color = Color::Rgb {
-                r: color[0],
-                g: color[1],
-                b: color[2],
+        }
+        color => color,
+    }
+}
+
+/// Light the rendered pixels of `columns` with the light map and the flashlight, the world
+/// position of each pixel being found back from its depth and the transforms of the ray of its
+/// column
+fn apply_lighting(
+    raycasting: &Raycasting,
+    projection: &Projection,
+    columns: &mut Columns,
+    ray_transforms: &[RayTransforms],
+) {
+    let width = raycasting.window.width();
+    let fov = raycasting.camera.horizontal_fov;
+    for x in columns.range() {
+        let angle_from_camera = fov / 2.0 - x as f64 * fov / (width - 1) as f64;
+        let ray_angle = raycasting.camera.angle + angle_from_camera;
+        let ray_direction = Vector2::new(ray_angle.cos(), -ray_angle.sin());
+        let flashlight_cone = if raycasting.player.is_flashlight_on {
+            f64::max(0.0, 1.0 - angle_from_camera.abs() / FLASHLIGHT_HALF_ANGLE)
+        } else {
+            0.0
+        };
+        for y in 0..raycasting.window.height() {
+            let distance = columns.get_depth(y, x);
+            let (position, euclidian_distance) = if distance.is_finite() {
+                let euclidian_distance = distance / angle_from_camera.cos();
+                // Step back a little so that walls are lit by the cell in front of them
+                let lit_distance = euclidian_distance - 0.01;
+                let position = raycasting.camera.position + ray_direction * lit_distance;
+                (
+                    get_ray_transform(&ray_transforms[x as usize], lit_distance).apply(&position),
+                    euclidian_distance,
+                )
+            } else {
+                // The ceiling is drawn behind everything, and the sky is not lit
+                let (position, euclidian_distance) = get_ceiling_position(
+                    raycasting,
+                    projection,
+                    &ray_transforms[x as usize],
+                    ray_angle,
+                    y,
+                );
+                if raycasting.map.cell_type_at(&position).is_outdoor {
+                    continue;
+                }
+                (position, euclidian_distance)
             };
-            raycasting.window.set_pixel(y, x, terminal_color);
-            image_y += image_y_step;
+            let light = raycasting.light_map.get_at(&position)
+                + FLASHLIGHT_INTENSITY
+                    * flashlight_cone
+                    * f64::max(0.0, 1.0 - euclidian_distance / FLASHLIGHT_RANGE);
+            let color = shade(columns.get_pixel(y, x), light);
+            columns.set_pixel(y, x, color);
         }
-        image_x += image_x_step;
     }
-    Ok(())
 }
 
-pub fn render(raycasting: &mut Raycasting) -> Result<()> {
-    let angle_increment =
-        -(raycasting.player.horizontal_fov / (raycasting.window.width() - 1) as f64);
-    let mut ray_angle = get_normalized_radians_angle(
-        raycasting.player.angle + raycasting.player.horizontal_fov / 2.0,
-    );
-    for x in 0..raycasting.window.width() {
-        render_column(raycasting, x, ray_angle)?;
-        ray_angle = get_normalized_radians_angle(ray_angle + angle_increment);
+/// Render the walls, floor and ceiling of `columns`, returning the transforms of the ray of each
+/// column, the distance up to which sprites are seen on it and the wall sprites it crosses
+fn render_columns<'a>(
+    raycasting: &'a Raycasting,
+    projection: &Projection,
+    columns: &mut Columns,
+) -> Vec<(RayTransforms, f64, Vec<WallSpriteHit<'a>>)> {
+    let fov = raycasting.camera.horizontal_fov;
+    let width = raycasting.window.width();
+    let mut rays = Vec::with_capacity(columns.range().len());
+    for x in columns.range() {
+        let ray_angle = get_normalized_radians_angle(
+            raycasting.camera.angle + fov / 2.0 - x as f64 * fov / (width - 1) as f64,
+        );
+        let ray_transforms = render_column(raycasting, projection, columns, x, ray_angle);
+        // Sprites are not seen through mirrors and portals, so not farther than the first one
+        let sprite_max_distance =
+            ray_transforms
+                .get(1)
+                .map_or(f64::INFINITY, |(euclidian_distance, _)| {
+                    euclidian_distance * (raycasting.camera.angle - ray_angle).cos()
+                });
+        // Wall sprites are drawn with the sprites, so that translucent ones blend in the right
+        // order
+        let wall_sprite_hits = get_wall_sprite_hits(raycasting, ray_angle, sprite_max_distance);
+        rays.push((ray_transforms, sprite_max_distance, wall_sprite_hits));
     }
+    rays
+}
+
+/// Get the number of threads rendering a frame `width` columns wide, at most `max_thread_count`
+fn get_render_thread_count(max_thread_count: usize, width: u16) -> usize {
+    usize::from(width / MIN_BAND_WIDTH).clamp(1, max_thread_count.max(1))
+}
+
+/// Run `render` on bands of adjacent columns of `framebuffer`, each on its own thread, returning
+/// what it returns for each band from left to right
+fn render_in_parallel<T: Send>(
+    framebuffer: &mut Framebuffer,
+    thread_count: usize,
+    render: impl Fn(&mut Columns) -> T + Sync,
+) -> Vec<T> {
+    if thread_count <= 1 {
+        return vec![render(&mut framebuffer.columns())];
+    }
+    let render = &render;
+    thread::scope(|scope| {
+        let handles: Vec<_> = framebuffer
+            .split_columns(thread_count)
+            .into_iter()
+            .map(|mut columns| scope.spawn(move || render(&mut columns)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Time spent in the passes of the rendering of a frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderTimes {
+    /// Walls, floor and ceiling, then lighting
+    pub ray_casting: Duration,
+    /// Sprites facing the viewer and wall sprites
+    pub sprite_projection: Duration,
+}
+
+fn render_framebuffer(raycasting: &Raycasting, framebuffer: &mut Framebuffer) -> RenderTimes {
+    let start = Instant::now();
+    let projection = Projection::new(raycasting);
+    let thread_count = get_render_thread_count(raycasting.render_thread_count, framebuffer.width());
+    let rays = render_in_parallel(framebuffer, thread_count, |columns| {
+        render_columns(raycasting, &projection, columns)
+    });
+    let mut ray_transforms = Vec::with_capacity(framebuffer.width().into());
+    let mut sprite_max_distances = Vec::with_capacity(framebuffer.width().into());
+    let mut wall_sprite_hits = Vec::with_capacity(framebuffer.width().into());
+    for (transforms, max_distance, hits) in rays.into_iter().flatten() {
+        ray_transforms.push(transforms);
+        sprite_max_distances.push(max_distance);
+        wall_sprite_hits.push(hits);
+    }
+    let mut ray_casting = start.elapsed();
+    let start = Instant::now();
+    // Sprites span several bands, and are drawn from the farthest to the nearest, each after the
+    // wall sprites behind it
     let sorted_window_sprites = get_sorted_window_sprites(raycasting);
+    let mut columns = framebuffer.columns();
     for window_sprite in sorted_window_sprites {
-        render_window_sprite(&window_sprite, raycasting)?;
+        render_window_sprite(
+            &window_sprite,
+            raycasting,
+            &projection,
+            &mut columns,
+            &sprite_max_distances,
+            &mut wall_sprite_hits,
+        );
+    }
+    for x in columns.range() {
+        render_wall_sprites_column(
+            raycasting,
+            &projection,
+            &mut columns,
+            x,
+            &mut wall_sprite_hits[x as usize],
+            f64::NEG_INFINITY,
+        );
+    }
+    let sprite_projection = start.elapsed();
+    let start = Instant::now();
+    render_in_parallel(framebuffer, thread_count, |columns| {
+        apply_lighting(raycasting, &projection, columns, &ray_transforms)
+    });
+    ray_casting += start.elapsed();
+    RenderTimes {
+        ray_casting,
+        sprite_projection,
     }
+}
+
+/// Render the frame seen by the camera into the framebuffer, without drawing it to the window,
+/// returning the time spent in each pass
+pub fn render_frame(raycasting: &mut Raycasting) -> RenderTimes {
+    let mut framebuffer = mem::take(&mut raycasting.framebuffer);
+    let times = render_framebuffer(raycasting, &mut framebuffer);
+    raycasting.framebuffer = framebuffer;
+    times
+}
+
+pub fn render(raycasting: &mut Raycasting) -> Result<()> {
+    render_frame(raycasting);
+    // The HUD is drawn over everything, and is not lit
+    if let Some(hud) = &mut raycasting.hud {
+        hud.update();
+        hud.draw_crosshair(&mut raycasting.framebuffer);
+    }
+    let reload_error = raycasting
+        .hot_reload
+        .as_ref()
+        .and_then(HotReload::error)
+        .map(|error| format!(" Reload error: {}", error));
+    let text = reload_error
+        .or_else(|| raycasting.hud.as_ref().map(|hud| hud.get_text(raycasting)))
+        .unwrap_or_default();
+    raycasting.window.set_text(&text);
+    raycasting.window.set_pixels(
+        raycasting.framebuffer.colors(),
+        raycasting.framebuffer.depths(),
+    );
     raycasting.window.redraw()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Instant};
+
+    use common::sprite::SpritePlacement;
+
+    use super::*;
+    use crate::config::{Config, Resolution};
+
+    #[test]
+    fn test_ray_transform() {
+        // Mirror on the right side of the cell (1, 0), then portal from (0, 0) to (5, 3)
+        let mirrored = RayTransform::IDENTITY.mirror(0, 2.0);
+        assert_eq!(
+            mirrored.apply_to_cell(&Vector2::new(2, 0)),
+            Vector2::new(1, 0)
+        );
+        assert_eq!(
+            mirrored.apply_to_cell(&Vector2::new(3, 0)),
+            Vector2::new(0, 0)
+        );
+        let teleported = mirrored.translate(Vector2::new(5.0, 3.0));
+        assert_eq!(
+            teleported.apply_to_cell(&Vector2::new(3, 0)),
+            Vector2::new(5, 3)
+        );
+        assert_eq!(
+            teleported.apply(&Point2::new(3.25, 0.5)),
+            Point2::new(5.75, 3.5)
+        );
+    }
+
+    #[test]
+    fn test_projection() {
+        let config = Config {
+            resolution: Resolution {
+                height: 20,
+                width: 30,
+            },
+            ..Config::default()
+        };
+        let mut raycasting = Raycasting::new(config, false, true).unwrap();
+        let projection = Projection::new(&raycasting);
+        assert_eq!(projection.horizon, 10.0);
+        assert_eq!(projection.y(projection.eye_height, 2.0), 10.0);
+        // Looking up moves everything down
+        raycasting.camera.pitch = 0.25;
+        let projection = Projection::new(&raycasting);
+        let horizon = 10.0 + 0.25_f64.tan() * 20.0;
+        assert!((projection.horizon - horizon).abs() < 1e-10);
+        assert_eq!(projection.y(projection.eye_height, 2.0), projection.horizon);
+        assert_eq!(projection.rows(0.0, 1.0, 2.0), (10, 20));
+        // The floor seen on a row is at the distance projecting it to the row center
+        let distance = projection.distance(0.0, 18);
+        assert!((projection.y(0.0, distance) - 18.5).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_intersect_segment() {
+        let origin = Point2::new(0.5, 0.5);
+        let start = Point2::new(2.0, 0.0);
+        let end = Point2::new(2.0, 2.0);
+        let (distance, position) =
+            intersect_segment(&origin, &Vector2::new(1.0, 0.0), &start, &end).unwrap();
+        assert!((distance - 1.5).abs() < 1e-10);
+        assert!((position - 0.25).abs() < 1e-10);
+        assert_eq!(
+            intersect_segment(&origin, &Vector2::new(-1.0, 0.0), &start, &end),
+            None
+        );
+        assert_eq!(
+            intersect_segment(&origin, &Vector2::new(0.0, 1.0), &start, &end),
+            None
+        );
+    }
+
+    #[test]
+    fn test_window_sprite_rows() {
+        let sprite_sheet = SpriteSheet::new(
+            io::Cursor::new(include_bytes!("../assets/sprites/penguin.png")),
+            Some(include_str!("../assets/sprites/penguin.toml")),
+        )
+        .unwrap();
+        let projection = Projection {
+            horizon: 10.0,
+            eye_height: 0.5,
+            scale: 20.0,
+            window_height: 20,
+        };
+        let mut window_sprite = WindowSprite::new(
+            String::from("penguin"),
+            Vector2::new(0, 0),
+            SpritePlacement {
+                height: Some(0.25),
+                ..SpritePlacement::default()
+            },
+            1.0,
+            0.0,
+            10,
+            2.0,
+        );
+        let (bottom_z, top_z) = get_window_sprite_heights(&window_sprite, &sprite_sheet);
+        assert_eq!(projection.rows(bottom_z, top_z, 2.0), (13, 15));
+        // The sprite sheet anchor defaults to the bottom of the frame, but hanging the sprite
+        // from the ceiling moves it to the top
+        window_sprite.placement.vertical_anchor = Some(VerticalAnchor::Ceiling);
+        let (bottom_z, top_z) = get_window_sprite_heights(&window_sprite, &sprite_sheet);
+        assert_eq!(projection.rows(bottom_z, top_z, 2.0), (5, 8));
+    }
+
+    #[test]
+    fn test_draw_sprite_pixel_occlusion() {
+        let wall = Color::Rgb { r: 1, g: 2, b: 3 };
+        let transparent = Rgba([255, 0, 0, 0]);
+        let opaque = Rgba([0, 255, 0, 255]);
+        let far_opaque = Rgba([0, 0, 255, 255]);
+        let mut framebuffer = Framebuffer::new(1, 2);
+        let mut columns = framebuffer.columns();
+        for x in 0..2 {
+            columns.set_pixel(0, x, wall);
+            columns.set_depth(0, x, 10.0);
+        }
+
+        // Transparent pixels leave the depth unchanged, so a farther sprite still shows through
+        draw_sprite_pixel(&mut columns, 0, 0, 2.0, &transparent, 1.0);
+        assert_eq!(columns.get_pixel(0, 0), wall);
+        assert_eq!(columns.get_depth(0, 0), 10.0);
+        draw_sprite_pixel(&mut columns, 0, 0, 5.0, &far_opaque, 1.0);
+        assert_eq!(columns.get_pixel(0, 0), Color::Rgb { r: 0, g: 0, b: 255 });
+        assert_eq!(columns.get_depth(0, 0), 5.0);
+
+        // Opaque pixels hide the farther sprites
+        draw_sprite_pixel(&mut columns, 0, 1, 2.0, &opaque, 1.0);
+        assert_eq!(columns.get_depth(0, 1), 2.0);
+        draw_sprite_pixel(&mut columns, 0, 1, 5.0, &far_opaque, 1.0);
+        assert_eq!(columns.get_pixel(0, 1), Color::Rgb { r: 0, g: 255, b: 0 });
+        assert_eq!(columns.get_depth(0, 1), 2.0);
+    }
+
+    #[test]
+    fn test_blend() {
+        let background = Color::Rgb {
+            r: 0,
+            g: 100,
+            b: 200,
+        };
+        assert_eq!(
+            blend(background, [200, 100, 0], 0.5),
+            Color::Rgb {
+                r: 100,
+                g: 100,
+                b: 100
+            }
+        );
+        assert_eq!(blend(background, [200, 100, 0], 0.0), background);
+        // The initial black is not RGB
+        assert_eq!(
+            blend(Color::Black, [200, 100, 0], 0.25),
+            Color::Rgb { r: 50, g: 25, b: 0 }
+        );
+    }
+
+    #[test]
+    fn test_draw_sprite_pixel_opacity() {
+        let background = Color::Rgb {
+            r: 0,
+            g: 100,
+            b: 200,
+        };
+        let mut framebuffer = Framebuffer::new(1, 1);
+        let mut columns = framebuffer.columns();
+        columns.set_pixel(0, 0, background);
+        columns.set_depth(0, 0, 10.0);
+        draw_sprite_pixel(&mut columns, 0, 0, 2.0, &Rgba([200, 100, 0, 255]), 0.5);
+        assert_eq!(
+            columns.get_pixel(0, 0),
+            Color::Rgb {
+                r: 100,
+                g: 100,
+                b: 100
+            }
+        );
+        // Translucent pixels do not hide what is behind them
+        assert_eq!(columns.get_depth(0, 0), 10.0);
+    }
+
+    #[test]
+    fn test_render_in_parallel() {
+        let config = Config {
+            resolution: Resolution {
+                height: 20,
+                width: MIN_BAND_WIDTH * 4,
+            },
+            ..Config::default()
+        };
+        assert_eq!(get_render_thread_count(4, MIN_BAND_WIDTH * 4), 4);
+        assert_eq!(get_render_thread_count(4, MIN_BAND_WIDTH * 2 - 1), 1);
+        let mut raycasting = Raycasting::new(config, false, true).unwrap();
+        raycasting.render_thread_count = 1;
+        render_frame(&mut raycasting);
+        let colors = raycasting.framebuffer.colors().clone();
+        raycasting.render_thread_count = 4;
+        render_frame(&mut raycasting);
+        assert_eq!(raycasting.framebuffer.colors(), &colors);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_render_width() {
+        const FRAMES: u32 = 100;
+        for width in [40, 80, 160, 320, 640] {
+            let config = Config {
+                resolution: Resolution {
+                    height: width * 9 / 16,
+                    width,
+                },
+                ..Config::default()
+            };
+            let mut raycasting = Raycasting::new(config, false, true).unwrap();
+            for render_thread_count in [1, 2, 4, 8] {
+                raycasting.render_thread_count = render_thread_count;
+                let start = Instant::now();
+                for frame in 0..FRAMES {
+                    raycasting.camera.angle = frame as f64 * f64::consts::TAU / FRAMES as f64;
+                    render_frame(&mut raycasting);
+                }
+                println!(
+                    "width {:>3}, {} threads ({} used): {:?} per frame",
+                    width,
+                    render_thread_count,
+                    get_render_thread_count(render_thread_count, width),
+                    start.elapsed() / FRAMES
+                );
+            }
+        }
+    }
+}
@@ -1,71 +1,429 @@
+use common::sprite::{AnimationState, SpritePlacement, VerticalAnchor};
 use image::io::Reader as ImageReader;
-use std::io::{BufRead, Seek};
+use image::{ImageError, RgbaImage};
+use nalgebra::Vector2;
+use serde::Deserialize;
+use std::{
+    error, f64, fmt,
+    io::{BufRead, Seek},
+};
 
-use image::DynamicImage;
+/// Animation frames are laid out one after the other, each animation following the previous one
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Animation {
+    pub name: String,
+    pub frames: u32,
+    /// Duration of a frame in seconds
+    pub frame_duration: f64,
+    /// Restart from the first frame once finished, else stay on the last one
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameOrder {
+    /// Each row holds a direction, the animation frames being laid out horizontally
+    DirectionsInRows,
+    /// Each column holds a direction, the animation frames being laid out vertically
+    DirectionsInColumns,
+}
+
+/// Content of the metadata file next to the sprite sheet image
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpriteSheetMetadata {
+    /// Width and height of a frame in pixels, computed from the image size if missing
+    pub frame_size: Option<[u32; 2]>,
+    /// Number of directions the sprite can be seen from: 1, 4, 8 or 16. The first direction is
+    /// the sprite facing the viewer, the next ones being turned counterclockwise as seen from
+    /// above
+    pub directions: u32,
+    pub frame_order: FrameOrder,
+    /// Pixel of a frame placed at the sprite position, the bottom center of the frame if missing,
+    /// or its top center if the sprite hangs from the ceiling
+    pub anchor: Option<[u32; 2]>,
+    /// World width of a frame, following the frame aspect ratio if missing
+    pub width: Option<f64>,
+    /// World height of a frame, following the frame aspect ratio if missing, or 1 if the width
+    /// is missing too
+    pub height: Option<f64>,
+    /// Place the anchor on the floor or the ceiling, the floor if missing
+    pub vertical_anchor: Option<VerticalAnchor>,
+    /// Distance of the anchor from the floor or the ceiling
+    pub vertical_offset: Option<f64>,
+    /// Color of the pixels to draw as transparent, eg. [255, 0, 255]
+    pub transparency_key: Option<[u8; 3]>,
+    pub animations: Vec<Animation>,
+}
+
+impl Default for SpriteSheetMetadata {
+    fn default() -> Self {
+        Self {
+            frame_size: None,
+            directions: 1,
+            frame_order: FrameOrder::DirectionsInRows,
+            anchor: None,
+            width: None,
+            height: None,
+            vertical_anchor: None,
+            vertical_offset: None,
+            transparency_key: None,
+            animations: vec![Animation {
+                name: String::from("idle"),
+                frames: 1,
+                frame_duration: 1.0,
+                looping: true,
+            }],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    Image(ImageError),
+    Metadata(toml::de::Error),
+    Layout(String),
+}
+
+impl fmt::Display for SpriteSheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpriteSheetError::Image(e) => write!(f, "sprite sheet image error: {}", e),
+            SpriteSheetError::Metadata(e) => write!(f, "sprite sheet metadata error: {}", e),
+            SpriteSheetError::Layout(message) => {
+                write!(f, "sprite sheet layout error: {}", message)
+            }
+        }
+    }
+}
+
+impl error::Error for SpriteSheetError {}
+
+impl From<ImageError> for SpriteSheetError {
+    fn from(e: ImageError) -> Self {
+        SpriteSheetError::Image(e)
+    }
+}
 
-pub enum SpriteSheetKind {
-    Single,
-    EightDirectional,
+impl From<toml::de::Error> for SpriteSheetError {
+    fn from(e: toml::de::Error) -> Self {
+        SpriteSheetError::Metadata(e)
+    }
 }
 
 pub struct SpriteSheet {
-    image: DynamicImage,
-    kind: SpriteSheetKind,
-    size: u32,
+    image: RgbaImage,
+    frame_size: Vector2<u32>,
+    directions: u32,
+    frame_order: FrameOrder,
+    /// Anchor given by the metadata, the default one depending on where the sprite is placed
+    anchor: Option<Vector2<u32>>,
+    placement: SpritePlacement,
+    animations: Vec<Animation>,
 }
 
 impl SpriteSheet {
-    pub fn new<R: BufRead + Seek>(buffered_reader: R) -> Self {
-        let image = ImageReader::new(buffered_reader)
+    /// Creates a sprite sheet from an image and the content of its metadata file, if any
+    pub fn new<R: BufRead + Seek>(
+        buffered_reader: R,
+        metadata: Option<&str>,
+    ) -> Result<Self, SpriteSheetError> {
+        let mut image = ImageReader::new(buffered_reader)
             .with_guessed_format()
-            .expect("Image reading error")
-            .decode()
-            .expect("Image decoding error");
-        assert_eq!(image.height() % image.width(), 0);
-        let kind = match image.height() / image.width() {
-            1 => SpriteSheetKind::Single,
-            8 => SpriteSheetKind::EightDirectional,
-            _ => panic!(
-                "Image width and height ratio not supported, width: {}, height: {}",
+            .map_err(ImageError::IoError)?
+            .decode()?
+            .into_rgba8();
+        let metadata = match metadata {
+            Some(metadata) => toml::from_str(metadata)?,
+            None => SpriteSheetMetadata::default(),
+        };
+        if ![1, 4, 8, 16].contains(&metadata.directions) {
+            return Err(SpriteSheetError::Layout(format!(
+                "{} directions is not one of 1, 4, 8 or 16",
+                metadata.directions
+            )));
+        }
+        if metadata.animations.is_empty() {
+            return Err(SpriteSheetError::Layout(String::from("no animations")));
+        }
+        if let Some(animation) = metadata.animations.iter().find(|animation| {
+            animation.frames == 0
+                || !(animation.frame_duration > 0.0 && animation.frame_duration.is_finite())
+        }) {
+            return Err(SpriteSheetError::Layout(format!(
+                "animation \"{}\" needs at least one frame and a positive frame duration",
+                animation.name
+            )));
+        }
+        let frames_count = metadata
+            .animations
+            .iter()
+            .try_fold(0_u32, |count, animation| {
+                count.checked_add(animation.frames)
+            })
+            .ok_or_else(|| SpriteSheetError::Layout(String::from("too many frames")))?;
+        let grid_size = match metadata.frame_order {
+            FrameOrder::DirectionsInRows => Vector2::new(frames_count, metadata.directions),
+            FrameOrder::DirectionsInColumns => Vector2::new(metadata.directions, frames_count),
+        };
+        let frame_size = match metadata.frame_size {
+            Some([width, height]) => Vector2::new(width, height),
+            None => Vector2::new(image.width() / grid_size.x, image.height() / grid_size.y),
+        };
+        let fits_in_image = |frame_size: u32, grid_size: u32, image_size: u32| {
+            frame_size
+                .checked_mul(grid_size)
+                .is_some_and(|size| size <= image_size)
+        };
+        if frame_size.x == 0
+            || frame_size.y == 0
+            || !fits_in_image(frame_size.x, grid_size.x, image.width())
+            || !fits_in_image(frame_size.y, grid_size.y, image.height())
+        {
+            return Err(SpriteSheetError::Layout(format!(
+                "image of {}x{} pixels too small for {}x{} frames of {}x{} pixels",
                 image.width(),
-                image.height()
-            ),
+                image.height(),
+                grid_size.x,
+                grid_size.y,
+                frame_size.x,
+                frame_size.y
+            )));
+        }
+        let anchor = match metadata.anchor {
+            Some([x, y]) if x <= frame_size.x && y <= frame_size.y => Some(Vector2::new(x, y)),
+            Some([x, y]) => {
+                return Err(SpriteSheetError::Layout(format!(
+                    "anchor ({}, {}) outside of the frame",
+                    x, y
+                )))
+            }
+            None => None,
         };
-        let size = image.width();
-        Self { image, kind, size }
+        if [metadata.width, metadata.height]
+            .iter()
+            .flatten()
+            .any(|&size| !(size > 0.0 && size.is_finite()))
+        {
+            return Err(SpriteSheetError::Layout(String::from(
+                "width and height must be positive",
+            )));
+        }
+        if let Some(transparency_key) = metadata.transparency_key {
+            for pixel in image.pixels_mut() {
+                if pixel.0[..3] == transparency_key {
+                    pixel.0[3] = 0;
+                }
+            }
+        }
+        Ok(Self {
+            image,
+            frame_size,
+            directions: metadata.directions,
+            frame_order: metadata.frame_order,
+            anchor,
+            placement: SpritePlacement {
+                width: metadata.width,
+                height: metadata.height,
+                vertical_anchor: metadata.vertical_anchor,
+                vertical_offset: metadata.vertical_offset,
+            },
+            animations: metadata.animations,
+        })
     }
 
-    pub fn image(&self) -> &DynamicImage {
+    pub fn image(&self) -> &RgbaImage {
         &self.image
     }
 
-    pub fn size(&self) -> u32 {
-        self.size
+    pub fn frame_size(&self) -> Vector2<u32> {
+        self.frame_size
     }
 
-    pub fn get_y_offset(&self, angle: f64) -> u32 {
-        let degrees = angle.to_degrees();
-        match &self.kind {
-            SpriteSheetKind::Single => 0,
-            SpriteSheetKind::EightDirectional => {
-                if degrees < 22.5 || 360.5 < degrees {
-                    0
-                } else if degrees < 67.5 {
-                    7 * self.size
-                } else if degrees < 112.5 {
-                    6 * self.size
-                } else if degrees < 157.5 {
-                    5 * self.size
-                } else if degrees < 202.5 {
-                    4 * self.size
-                } else if degrees < 247.5 {
-                    3 * self.size
-                } else if degrees < 292.5 {
-                    2 * self.size
-                } else {
-                    self.size
-                }
-            },
+    /// Get the pixel of a frame placed at the sprite position when it is anchored to
+    /// `vertical_anchor`
+    pub fn get_anchor(&self, vertical_anchor: VerticalAnchor) -> Vector2<u32> {
+        self.anchor.unwrap_or_else(|| match vertical_anchor {
+            VerticalAnchor::Floor => Vector2::new(self.frame_size.x / 2, self.frame_size.y),
+            VerticalAnchor::Ceiling => Vector2::new(self.frame_size.x / 2, 0),
+        })
+    }
+
+    /// Placement of the sprites using this sprite sheet, unless they override it
+    pub fn placement(&self) -> SpritePlacement {
+        self.placement
+    }
+
+    /// Get the world width and height of a frame placed with `placement`
+    pub fn get_world_size(&self, placement: &SpritePlacement) -> Vector2<f64> {
+        let aspect_ratio = self.frame_size.x as f64 / self.frame_size.y as f64;
+        match (placement.width, placement.height) {
+            (Some(width), Some(height)) => Vector2::new(width, height),
+            (Some(width), None) => Vector2::new(width, width / aspect_ratio),
+            (None, Some(height)) => Vector2::new(height * aspect_ratio, height),
+            (None, None) => Vector2::new(aspect_ratio, 1.0),
+        }
+    }
+
+    fn get_frame_index(&self, animation_state: &AnimationState) -> u32 {
+        let animation_index = self
+            .animations
+            .iter()
+            .position(|animation| animation.name == animation_state.name)
+            .unwrap_or(0);
+        let first_frame: u32 = self.animations[..animation_index]
+            .iter()
+            .map(|animation| animation.frames)
+            .sum();
+        let animation = &self.animations[animation_index];
+        let elapsed_frames = (animation_state.time / animation.frame_duration) as u32;
+        let frame = if animation.looping {
+            elapsed_frames % animation.frames
+        } else {
+            u32::min(elapsed_frames, animation.frames - 1)
+        };
+        first_frame + frame
+    }
+
+    /// Get the direction index from the angle between the sprite direction and the viewer
+    fn get_direction_index(&self, angle: f64) -> u32 {
+        let direction_angle = f64::consts::TAU / self.directions as f64;
+        let index = (angle / direction_angle).round() as u32 % self.directions;
+        (self.directions - index) % self.directions
+    }
+
+    /// Get the offset in the image of the current frame of `animation_state`, seen from `angle`
+    /// if the sprite has a direction
+    pub fn get_frame_offset(
+        &self,
+        animation_state: &AnimationState,
+        angle: Option<f64>,
+    ) -> Vector2<u32> {
+        let frame_index = self.get_frame_index(animation_state);
+        let direction_index = angle.map_or(0, |angle| self.get_direction_index(angle));
+        let grid_position = match self.frame_order {
+            FrameOrder::DirectionsInRows => Vector2::new(frame_index, direction_index),
+            FrameOrder::DirectionsInColumns => Vector2::new(direction_index, frame_index),
+        };
+        grid_position.component_mul(&self.frame_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_sprite_sheet_layout() {
+        let penguin = include_bytes!("../assets/sprites/penguin.png");
+        let sprite_sheet = SpriteSheet::new(
+            Cursor::new(penguin),
+            Some(include_str!("../assets/sprites/penguin.toml")),
+        )
+        .unwrap();
+        assert_eq!(sprite_sheet.frame_size(), Vector2::new(16, 16));
+        assert_eq!(
+            sprite_sheet.get_anchor(VerticalAnchor::Floor),
+            Vector2::new(8, 16)
+        );
+        assert_eq!(
+            sprite_sheet.get_anchor(VerticalAnchor::Ceiling),
+            Vector2::new(8, 0)
+        );
+        let idle = AnimationState::default();
+        assert_eq!(
+            sprite_sheet.get_frame_offset(&idle, None),
+            Vector2::new(0, 0)
+        );
+        assert_eq!(
+            sprite_sheet.get_frame_offset(&idle, Some(90_f64.to_radians())),
+            Vector2::new(0, 6 * 16)
+        );
+        assert_eq!(
+            sprite_sheet.get_frame_offset(&idle, Some(350_f64.to_radians())),
+            Vector2::new(0, 0)
+        );
+
+        let placement = SpritePlacement {
+            width: Some(0.5),
+            ..SpritePlacement::default()
+        };
+        assert_eq!(
+            sprite_sheet.get_world_size(&placement),
+            Vector2::new(0.5, 0.5)
+        );
+
+        let error = SpriteSheet::new(Cursor::new(penguin), Some("directions = 3")).err();
+        assert!(matches!(error, Some(SpriteSheetError::Layout(_))));
+        let error = SpriteSheet::new(
+            Cursor::new(penguin),
+            Some("directions = 8\nframe_size = [32, 32]"),
+        )
+        .err();
+        assert!(matches!(error, Some(SpriteSheetError::Layout(_))));
+        for metadata in [
+            "animations = [{ name = \"idle\", frames = 1, frame_duration = nan }]",
+            "animations = [{ name = \"idle\", frames = 1, frame_duration = -1.0 }]",
+            "animations = [{ name = \"a\", frames = 4294967295, frame_duration = 1.0 }, \
+                { name = \"b\", frames = 1, frame_duration = 1.0 }]",
+            "frame_size = [65536, 65536]\n\
+                animations = [{ name = \"a\", frames = 65536, frame_duration = 1.0 }]",
+        ] {
+            let error = SpriteSheet::new(Cursor::new(penguin), Some(metadata)).err();
+            assert!(matches!(error, Some(SpriteSheetError::Layout(_))));
         }
     }
+
+    #[test]
+    fn test_animations() {
+        // The penguin image is 8 frames high
+        let sprite_sheet = SpriteSheet::new(
+            Cursor::new(include_bytes!("../assets/sprites/penguin.png")),
+            Some(
+                r#"
+                frame_size = [16, 16]
+                frame_order = "directions_in_columns"
+
+                [[animations]]
+                name = "walk"
+                frames = 3
+                frame_duration = 0.5
+
+                [[animations]]
+                name = "die"
+                frames = 2
+                frame_duration = 0.25
+                looping = false
+                "#,
+            ),
+        )
+        .unwrap();
+        let get_frame_offset = |name: &str, time: f64| {
+            let animation_state = AnimationState {
+                name: String::from(name),
+                time,
+            };
+            sprite_sheet.get_frame_offset(&animation_state, None)
+        };
+        assert_eq!(get_frame_offset("walk", 0.0), Vector2::new(0, 0));
+        assert_eq!(get_frame_offset("walk", 0.6), Vector2::new(0, 16));
+        assert_eq!(get_frame_offset("walk", 1.2), Vector2::new(0, 32));
+        // Looping back to the first frame
+        assert_eq!(get_frame_offset("walk", 1.6), Vector2::new(0, 0));
+        // The second animation starts after the frames of the first one
+        assert_eq!(get_frame_offset("die", 0.0), Vector2::new(0, 48));
+        assert_eq!(get_frame_offset("die", 0.3), Vector2::new(0, 64));
+        // Staying on the last frame
+        assert_eq!(get_frame_offset("die", 10.0), Vector2::new(0, 64));
+        // Unknown animations fall back to the first one
+        assert_eq!(get_frame_offset("fly", 0.6), Vector2::new(0, 16));
+    }
 }
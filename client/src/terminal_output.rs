@@ -0,0 +1,167 @@
+use std::io::Write;
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Colors, Print, SetBackgroundColor, SetColors, SetForegroundColor},
+    Result,
+};
+use nalgebra::{DMatrix, Point2, Vector2};
+
+/// Character drawn in a terminal cell, with its colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Cell {
+    /// Get a cell showing `symbol` with the default colors of the terminal
+    pub fn text(symbol: char) -> Self {
+        Self {
+            symbol,
+            foreground: Color::Reset,
+            background: Color::Reset,
+        }
+    }
+}
+
+/// Terminal output sending only the cells that changed since the previous frame, in a single write
+#[derive(Debug, Default)]
+pub struct TerminalOutput {
+    /// Terminal position of the first cell of the previous frame, and its cells
+    previous: Option<(Point2<u16>, DMatrix<Cell>)>,
+    /// Commands of the frame being written
+    buffer: Vec<u8>,
+    frame_bytes: usize,
+}
+
+impl TerminalOutput {
+    /// Forget the previous frame, eg. once the terminal is cleared, so that the next one is fully
+    /// written
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    /// Number of bytes written for the last frame
+    pub fn frame_bytes(&self) -> usize {
+        self.frame_bytes
+    }
+
+    /// Write to `out` the commands drawing `cells` from the terminal `position`, skipping the
+    /// cells that did not change since the previous frame.
+    ///
+    /// The cursor is only moved between changed cells that are not next to each other, and colors
+    /// are only set when they differ from the ones of the previous changed cell.
+    pub fn write_frame(
+        &mut self,
+        position: Point2<u16>,
+        cells: DMatrix<Cell>,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        self.buffer.clear();
+        let previous = self
+            .previous
+            .take()
+            .filter(|(previous_position, previous_cells)| {
+                *previous_position == position && previous_cells.shape() == cells.shape()
+            })
+            .map(|(_, previous_cells)| previous_cells);
+        let mut cursor = None;
+        let mut foreground = Color::Reset;
+        let mut background = Color::Reset;
+        for row in 0..cells.nrows() {
+            for column in 0..cells.ncols() {
+                let cell = cells[(row, column)];
+                if previous
+                    .as_ref()
+                    .is_some_and(|previous_cells| previous_cells[(row, column)] == cell)
+                {
+                    continue;
+                }
+                let cell_position =
+                    Point2::new(position.x + column as u16, position.y + row as u16);
+                if cursor != Some(cell_position) {
+                    queue!(self.buffer, MoveTo(cell_position.x, cell_position.y))?;
+                }
+                if cell.foreground != foreground {
+                    queue!(self.buffer, SetForegroundColor(cell.foreground))?;
+                    foreground = cell.foreground;
+                }
+                if cell.background != background {
+                    queue!(self.buffer, SetBackgroundColor(cell.background))?;
+                    background = cell.background;
+                }
+                queue!(self.buffer, Print(cell.symbol))?;
+                cursor = Some(cell_position + Vector2::new(1, 0));
+            }
+        }
+        if foreground != Color::Reset || background != Color::Reset {
+            queue!(
+                self.buffer,
+                SetColors(Colors::new(Color::Reset, Color::Reset))
+            )?;
+        }
+        out.write_all(&self.buffer)?;
+        self.frame_bytes = self.buffer.len();
+        self.previous = Some((position, cells));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_frame() {
+        let red = Cell {
+            symbol: '▀',
+            foreground: Color::Red,
+            background: Color::Blue,
+        };
+        let mut cells = DMatrix::from_element(2, 3, red);
+        let mut output = TerminalOutput::default();
+        let mut out = Vec::new();
+        output
+            .write_frame(Point2::new(4, 1), cells.clone(), &mut out)
+            .unwrap();
+        // The colors are set once for the whole run of identical cells
+        let mut expected = Vec::new();
+        queue!(
+            expected,
+            MoveTo(4, 1),
+            SetForegroundColor(Color::Red),
+            SetBackgroundColor(Color::Blue),
+            Print("▀▀▀"),
+            MoveTo(4, 2),
+            Print("▀▀▀"),
+            SetColors(Colors::new(Color::Reset, Color::Reset))
+        )
+        .unwrap();
+        assert_eq!(out, expected);
+        assert_eq!(output.frame_bytes(), expected.len());
+
+        out.clear();
+        output
+            .write_frame(Point2::new(4, 1), cells.clone(), &mut out)
+            .unwrap();
+        assert!(out.is_empty());
+
+        cells[(1, 2)] = Cell::text('@');
+        output
+            .write_frame(Point2::new(4, 1), cells.clone(), &mut out)
+            .unwrap();
+        let mut expected = Vec::new();
+        queue!(expected, MoveTo(6, 2), Print('@')).unwrap();
+        assert_eq!(out, expected);
+
+        out.clear();
+        output.invalidate();
+        output
+            .write_frame(Point2::new(4, 1), cells, &mut out)
+            .unwrap();
+        assert!(out.len() > expected.len());
+    }
+}
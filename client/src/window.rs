@@ -0,0 +1,525 @@
+use std::cmp;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, Event::Key, Event::Resize, KeyCode,
+    KeyEventKind, KeyModifiers,
+};
+use crossterm::style::{Color, Print};
+use crossterm::terminal::{
+    Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{execute, queue, terminal, Result};
+
+use nalgebra::{DMatrix, Point2, Vector2};
+
+use crate::{
+    color_mode::{self, ColorMode},
+    recording::Recording,
+    terminal_output::{Cell, TerminalOutput},
+};
+
+const UPPER_HALF_BLOCK: char = '▀';
+const LOWER_HALF_BLOCK: char = '▄';
+const FULL_BLOCK: char = '█';
+
+/// Braille character with no raised dots, the others adding the bits of their dots
+const BRAILLE_BLANK: u32 = 0x2800;
+/// Bit of each dot of a braille character, by (x, y) position in its 2x4 cell
+const BRAILLE_DOTS: [(usize, usize, u32); 8] = [
+    (0, 0, 0x01),
+    (0, 1, 0x02),
+    (0, 2, 0x04),
+    (1, 0, 0x08),
+    (1, 1, 0x10),
+    (1, 2, 0x20),
+    (0, 3, 0x40),
+    (1, 3, 0x80),
+];
+/// Luminance difference between two neighboring pixels above which a braille dot is raised
+const BRAILLE_EDGE_THRESHOLD: f64 = 0.08;
+/// Bend of the inverse depth of three neighboring pixels, relative to its largest value, above
+/// which a braille dot is raised. The inverse depth of a flat surface changing about linearly
+/// across the window, it bends where two surfaces meet, eg. at wall corners, or jumps where one
+/// hides another.
+const BRAILLE_DEPTH_EDGE_THRESHOLD: f64 = 0.002;
+
+/// Window representation, based on the [winterm](https://github.com/clbrunet/winterm) one.
+/// Used for drawing and events handling.
+#[derive(Debug)]
+pub struct Window {
+    terminal_size: Vector2<u16>,
+    origin: Point2<i16>,
+    pixels: DMatrix<Color>,
+    /// Distance of what each pixel shows, infinite for the ceiling and the sky
+    depths: DMatrix<f64>,
+    last_events: Vec<Event>,
+    mouse_capture: bool,
+    color_mode: ColorMode,
+    /// Not drawn to the terminal, eg. for benchmarks
+    is_headless: bool,
+    /// Characters of the row of text above the pixels, if the window has one
+    text_row: Option<Vec<char>>,
+    output: TerminalOutput,
+    recording: Option<Recording>,
+}
+
+impl Window {
+    fn calculate_origin(&mut self) {
+        self.origin.x = (self.terminal_size.x as f32 / 2. - self.columns() as f32 / 2.) as i16;
+        self.origin.y = (self.terminal_size.y as f32 / 2. - self.rows() as f32 / 2.) as i16;
+    }
+
+    /// Creates a window, capturing mouse events if `mouse_capture` is `true`, drawing pixels
+    /// with the colors of `color_mode` and reserving a row of text above them if `text_row` is
+    /// `true`.
+    pub fn new(
+        height: u16,
+        width: u16,
+        mouse_capture: bool,
+        color_mode: ColorMode,
+        text_row: bool,
+    ) -> Result<Self> {
+        let (columns, rows) = terminal::size()?;
+        // Created before setting up the terminal, so that dropping it on error undoes the setup
+        let mut window = Window {
+            terminal_size: Vector2::new(columns, rows),
+            origin: Point2::origin(),
+            pixels: DMatrix::from_element(height.into(), width.into(), Color::Black),
+            depths: DMatrix::from_element(height.into(), width.into(), f64::INFINITY),
+            last_events: Vec::new(),
+            mouse_capture,
+            color_mode,
+            is_headless: false,
+            text_row: text_row.then(Vec::new),
+            output: TerminalOutput::default(),
+            recording: None,
+        };
+        execute!(stdout(), EnterAlternateScreen, DisableLineWrap, Hide)?;
+        if mouse_capture {
+            execute!(stdout(), EnableMouseCapture)?;
+        }
+        terminal::enable_raw_mode()?;
+        window.calculate_origin();
+        window.redraw_all()?;
+        Ok(window)
+    }
+
+    /// Creates a window that is not drawn to the terminal and does not read events, as if the
+    /// terminal had its size.
+    pub fn headless(height: u16, width: u16, color_mode: ColorMode, text_row: bool) -> Self {
+        let mut window = Window {
+            terminal_size: Vector2::zeros(),
+            origin: Point2::origin(),
+            pixels: DMatrix::from_element(height.into(), width.into(), Color::Black),
+            depths: DMatrix::from_element(height.into(), width.into(), f64::INFINITY),
+            last_events: Vec::new(),
+            mouse_capture: false,
+            color_mode,
+            is_headless: true,
+            text_row: text_row.then(Vec::new),
+            output: TerminalOutput::default(),
+            recording: None,
+        };
+        window.terminal_size = Vector2::new(window.columns(), window.rows());
+        window
+    }
+
+    /// Gets the window width.
+    pub fn width(&self) -> u16 {
+        self.pixels.ncols() as u16
+    }
+
+    /// Gets the window height.
+    pub fn height(&self) -> u16 {
+        self.pixels.nrows() as u16
+    }
+
+    /// Number of terminal columns the window takes.
+    fn columns(&self) -> u16 {
+        self.width().div_ceil(self.color_mode.cell_size().x)
+    }
+
+    /// Number of terminal rows the window takes.
+    fn rows(&self) -> u16 {
+        u16::from(self.text_row.is_some()) + self.height().div_ceil(self.color_mode.cell_size().y)
+    }
+
+    /// Returns `true` if the pixels only fill the upper half of the last row, its lower half
+    /// being the border.
+    fn has_half_last_row(&self) -> bool {
+        self.color_mode.cell_size().y == 2 && self.height() % 2 == 1
+    }
+
+    fn end_x(&self) -> u16 {
+        (self.origin.x + self.columns() as i16) as u16
+    }
+
+    fn end_y(&self) -> u16 {
+        (self.origin.y + self.rows() as i16) as u16
+    }
+
+    /// Sets the color and the distance of what is drawn of every pixel, `pixels` and `depths`
+    /// having the window size.
+    pub fn set_pixels(&mut self, pixels: &DMatrix<Color>, depths: &DMatrix<f64>) {
+        self.pixels.copy_from(pixels);
+        self.depths.copy_from(depths);
+    }
+
+    /// Gets the character shading a cell made of the pixels `colors`, for the ASCII color mode.
+    fn get_ascii_char(colors: &[Color]) -> char {
+        let luminance = colors
+            .iter()
+            .map(|&color| color_mode::get_rgb(color).map_or(0.0, color_mode::get_luminance))
+            .sum::<f64>()
+            / colors.len() as f64;
+        color_mode::get_ascii_char(luminance)
+    }
+
+    /// Gets the luminance of the pixel at (y, x), non-RGB colors being black.
+    fn get_luminance(&self, y: usize, x: usize) -> f64 {
+        color_mode::get_rgb(self.pixels[(y, x)]).map_or(0.0, color_mode::get_luminance)
+    }
+
+    /// Gets how much the inverse depth bends at the pixel at (y, x) between its neighbors one
+    /// `step` before and after it, relative to its largest value, 0 on the window border.
+    fn get_depth_bend(&self, y: usize, x: usize, step: (usize, usize)) -> f64 {
+        let (Some(before_y), Some(before_x)) = (y.checked_sub(step.0), x.checked_sub(step.1))
+        else {
+            return 0.0;
+        };
+        let (after_y, after_x) = (y + step.0, x + step.1);
+        if after_y >= self.depths.nrows() || after_x >= self.depths.ncols() {
+            return 0.0;
+        }
+        let [before, current, after] = [(before_y, before_x), (y, x), (after_y, after_x)]
+            .map(|position| 1.0 / self.depths[position]);
+        let largest = before.max(current).max(after);
+        if largest > 0.0 {
+            (before - 2.0 * current + after).abs() / largest
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns `true` if the inverse depth bends at the pixel at (y, x) more than at the pixel
+    /// before it and at least as much as at the pixel after it, horizontally or vertically, so
+    /// that edges are one pixel thick.
+    fn is_depth_edge(&self, y: usize, x: usize) -> bool {
+        [(0, 1), (1, 0)].into_iter().any(|step| {
+            let bend = self.get_depth_bend(y, x, step);
+            bend > BRAILLE_DEPTH_EDGE_THRESHOLD
+                && bend > self.get_depth_bend(y - step.0, x - step.1, step)
+                && bend >= self.get_depth_bend(y + step.0, x + step.1, step)
+        })
+    }
+
+    /// Returns `true` if the luminance of the pixel at (y, x) differs from the one of the pixel
+    /// below it or on its right, or if what it shows is at the edge of a surface.
+    fn is_edge(&self, y: usize, x: usize) -> bool {
+        let luminance = self.get_luminance(y, x);
+        [(y + 1, x), (y, x + 1)]
+            .into_iter()
+            .filter(|&(y, x)| y < self.pixels.nrows() && x < self.pixels.ncols())
+            .any(|(y, x)| (self.get_luminance(y, x) - luminance).abs() > BRAILLE_EDGE_THRESHOLD)
+            || self.is_depth_edge(y, x)
+    }
+
+    /// Gets the braille character of the cell at (row, column), whose dots are raised on the
+    /// edges of what is drawn.
+    fn get_braille_char(&self, row: usize, column: usize) -> char {
+        let bits = BRAILLE_DOTS
+            .iter()
+            .map(|&(dx, dy, bit)| (column * 2 + dx, row * 4 + dy, bit))
+            .filter(|&(x, y, _)| y < self.pixels.nrows() && x < self.pixels.ncols())
+            .filter(|&(x, y, _)| self.is_edge(y, x))
+            .fold(0, |bits, (_, _, bit)| bits | bit);
+        char::from_u32(BRAILLE_BLANK + bits).unwrap()
+    }
+
+    /// Sets the text of the row above the pixels, truncated to the window width, if the window
+    /// has one. Control characters such as line breaks are replaced by spaces.
+    pub fn set_text(&mut self, text: &str) {
+        if let Some(text_row) = &mut self.text_row {
+            text_row.clear();
+            text_row.extend(
+                text.chars()
+                    .map(|char| if char.is_control() { ' ' } else { char }),
+            );
+        }
+    }
+
+    /// Gets the terminal cell at (row, column) of the window.
+    fn get_cell(&self, row: usize, column: usize) -> Cell {
+        if let Some(text_row) = &self.text_row {
+            if row == 0 {
+                return Cell::text(text_row.get(column).copied().unwrap_or(' '));
+            }
+            return self.get_pixels_cell(row - 1, column);
+        }
+        self.get_pixels_cell(row, column)
+    }
+
+    /// Gets the terminal cell at (row, column) of the pixels.
+    fn get_pixels_cell(&self, row: usize, column: usize) -> Cell {
+        if self.color_mode == ColorMode::Braille {
+            return Cell::text(self.get_braille_char(row, column));
+        }
+        let y = row * 2;
+        let upper = self.pixels[(y, column)];
+        let lower = (y + 1 < self.pixels.nrows()).then(|| self.pixels[(y + 1, column)]);
+        match (self.color_mode, lower) {
+            (ColorMode::Ascii, Some(lower)) => Cell::text(Self::get_ascii_char(&[upper, lower])),
+            (ColorMode::Ascii, None) => Cell::text(Self::get_ascii_char(&[upper])),
+            (_, Some(lower)) => Cell {
+                symbol: UPPER_HALF_BLOCK,
+                foreground: self.color_mode.convert(upper, column, y),
+                background: self.color_mode.convert(lower, column, y + 1),
+            },
+            // The lower half of the last row is the border
+            (_, None) => Cell {
+                symbol: LOWER_HALF_BLOCK,
+                foreground: Color::Reset,
+                background: self.color_mode.convert(upper, column, y),
+            },
+        }
+    }
+
+    /// Redraws the window to the terminal, only writing the cells that changed since the last
+    /// redraw.
+    pub fn redraw(&mut self) -> Result<()> {
+        let skipable_rows_count = cmp::max(-self.origin.y, 0) as usize;
+        let skipable_columns_count = cmp::max(-self.origin.x, 0) as usize;
+        let position = Point2::new(
+            cmp::max(self.origin.x, 0) as u16,
+            cmp::max(self.origin.y, 0) as u16,
+        );
+        let rows = cmp::min(self.end_y(), self.terminal_size.y).saturating_sub(position.y);
+        let columns = cmp::min(
+            self.columns() as usize - cmp::min(skipable_columns_count, self.columns() as usize),
+            self.terminal_size.x as usize,
+        );
+        let cells = DMatrix::from_fn(rows.into(), columns, |row, column| {
+            self.get_cell(skipable_rows_count + row, skipable_columns_count + column)
+        });
+        // Still written when headless, so that benchmarks measure it
+        let mut frame = Vec::new();
+        self.output.write_frame(position, cells, &mut frame)?;
+        self.write_to_terminal(&frame)
+    }
+
+    /// Writes `bytes` to the terminal at once, and to the recording if there is one.
+    fn write_to_terminal(&mut self, bytes: &[u8]) -> Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.write_output(bytes)?;
+        }
+        if self.is_headless {
+            return Ok(());
+        }
+        let mut out = stdout();
+        out.write_all(bytes)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Starts saving what is written to the terminal to an asciinema file at `path`, beginning
+    /// with the terminal setup and a full redraw of the window.
+    pub fn start_recording(&mut self, path: &Path) -> Result<()> {
+        let mut recording = Recording::create(path, self.terminal_size)?;
+        // Written by `new` before the recording started, but needed to replay it
+        let mut setup = Vec::new();
+        queue!(setup, EnterAlternateScreen, DisableLineWrap, Hide)?;
+        recording.write_output(&setup)?;
+        self.recording = Some(recording);
+        self.redraw_all()
+    }
+
+    /// Gets the number of bytes written to the terminal by the last redraw.
+    pub fn frame_bytes(&self) -> usize {
+        self.output.frame_bytes()
+    }
+
+    fn redraw_border(&self, out: &mut impl Write) -> Result<()> {
+        if self.origin.y > 0 {
+            queue!(
+                out,
+                MoveTo(
+                    cmp::max(self.origin.x - 1, 0) as u16,
+                    (self.origin.y - 1) as u16
+                ),
+                Print(
+                    LOWER_HALF_BLOCK
+                        .to_string()
+                        .repeat(cmp::min(self.columns() + 2, self.terminal_size.x).into())
+                )
+            )?;
+        }
+        let range = cmp::max(self.origin.y, 0) as u16..cmp::min(self.end_y(), self.terminal_size.y);
+        if self.origin.x > 0 {
+            for y in range.clone() {
+                queue!(
+                    out,
+                    MoveTo((self.origin.x - 1) as u16, y),
+                    Print(FULL_BLOCK)
+                )?;
+            }
+        }
+        if self.end_x() < self.terminal_size.x {
+            for y in range {
+                queue!(out, MoveTo(self.end_x(), y), Print(FULL_BLOCK))?;
+            }
+        }
+        if !self.has_half_last_row() && self.end_y() < self.terminal_size.y {
+            queue!(
+                out,
+                MoveTo(cmp::max(self.origin.x - 1, 0) as u16, self.end_y()),
+                Print(
+                    UPPER_HALF_BLOCK
+                        .to_string()
+                        .repeat(cmp::min(self.columns() + 2, self.terminal_size.x).into())
+                )
+            )?;
+        }
+        Ok(())
+    }
+
+    fn redraw_all(&mut self) -> Result<()> {
+        let mut bytes = Vec::new();
+        queue!(bytes, Clear(ClearType::All))?;
+        self.redraw_border(&mut bytes)?;
+        self.write_to_terminal(&bytes)?;
+        self.output.invalidate();
+        self.redraw()?;
+        Ok(())
+    }
+
+    /// Clears events and polls for newer events.
+    pub fn poll_events(&mut self) -> Result<()> {
+        self.last_events.clear();
+        if self.is_headless {
+            return Ok(());
+        }
+        while event::poll(Duration::from_secs(0))? {
+            self.last_events.push(event::read()?);
+            if let Resize(columns, rows) = self.last_events.last().unwrap() {
+                self.terminal_size.x = *columns;
+                self.terminal_size.y = *rows;
+                self.calculate_origin();
+                if let Some(recording) = &mut self.recording {
+                    recording.write_resize(self.terminal_size)?;
+                }
+                self.redraw_all()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `key` was read during the last call to [`Window::poll_events`].
+    pub fn get_key(&mut self, key: KeyCode) -> bool {
+        self.last_events
+            .iter()
+            .any(|event| Self::is_key_event(event, key))
+    }
+
+    /// Returns `true` if `key` was pressed during the last call to [`Window::poll_events`],
+    /// ignoring the repeat and release events of the terminals reporting them.
+    pub fn get_key_press(&mut self, key: KeyCode) -> bool {
+        self.last_events.iter().any(|event| {
+            matches!(event, Key(key_event) if key_event.kind == KeyEventKind::Press)
+                && Self::is_key_event(event, key)
+        })
+    }
+
+    /// Returns `true` if `event` is an event of `key`, letters being case insensitive.
+    fn is_key_event(event: &Event, key: KeyCode) -> bool {
+        if let Key(key_event) = *event {
+            if key_event.code == key {
+                return true;
+            }
+            if let (KeyCode::Char(char), KeyCode::Char(event_char)) = (key, key_event.code) {
+                if char.to_lowercase().to_string() == event_char.to_lowercase().to_string() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns `true` if a key event with `modifiers` was read during the last call to
+    /// [`Window::poll_events`].
+    pub fn get_modifiers(&self, modifiers: KeyModifiers) -> bool {
+        self.last_events.iter().any(|event| {
+            if let Key(key_event) = *event {
+                return key_event.modifiers.contains(modifiers);
+            }
+            false
+        })
+    }
+
+    /// Gets the events read during the last call to [`Window::poll_events`].
+    pub fn events(&self) -> &[Event] {
+        &self.last_events
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        if self.is_headless {
+            return;
+        }
+        if self.mouse_capture {
+            let _ = execute!(stdout(), DisableMouseCapture);
+        }
+        let _ = execute!(stdout(), LeaveAlternateScreen, EnableLineWrap, Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the braille characters of a window of 4 rows of `colors` and inverse `depths`, by
+    /// column.
+    fn get_braille_chars(colors: &[Color], inverse_depths: &[f64]) -> String {
+        let mut window = Window::headless(4, colors.len() as u16, ColorMode::Braille, false);
+        window.set_pixels(
+            &DMatrix::from_fn(4, colors.len(), |_, x| colors[x]),
+            &DMatrix::from_fn(4, inverse_depths.len(), |_, x| 1.0 / inverse_depths[x]),
+        );
+        (0..colors.len() / 2)
+            .map(|column| window.get_braille_char(0, column))
+            .collect()
+    }
+
+    #[test]
+    fn test_get_braille_char() {
+        let red = Color::Rgb { r: 200, g: 0, b: 0 };
+        let shaded_red = Color::Rgb { r: 160, g: 0, b: 0 };
+        // Same surface
+        assert_eq!(get_braille_chars(&[red; 4], &[0.5; 4]), "⠀⠀");
+        // Nearer wall hiding a farther one of the same color
+        assert_eq!(get_braille_chars(&[red; 4], &[0.5, 0.5, 0.25, 0.25]), "⢸⠀");
+        // Corner between two wall sides, their shades being too close to be an edge
+        assert_eq!(
+            get_braille_chars(
+                &[red, red, red, shaded_red, shaded_red, shaded_red],
+                &[0.1, 0.2, 0.3, 0.3, 0.3, 0.3]
+            ),
+            "⠀⡇⠀"
+        );
+        // Luminance edge, on the pixel left of the change
+        let white = Color::Rgb {
+            r: 255,
+            g: 255,
+            b: 255,
+        };
+        assert_eq!(
+            get_braille_chars(&[red, red, white, white], &[0.5; 4]),
+            "⢸⠀"
+        );
+    }
+}
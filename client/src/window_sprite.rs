@@ -1,22 +1,39 @@
 use std::f64;
 
-use common::get_normalized_radians_angle;
+use common::{get_normalized_radians_angle, sprite::SpritePlacement};
+use nalgebra::Vector2;
 
 use crate::Raycasting;
 
 #[derive(Debug)]
 pub struct WindowSprite {
-    pub sprite_sheet_index: usize,
-    pub sprite_sheet_y_offset: u32,
+    pub sprite_sheet: String,
+    pub sprite_sheet_offset: Vector2<u32>,
+    /// Placement of the sprite, completed with the sprite sheet one
+    pub placement: SpritePlacement,
+    pub opacity: f64,
+    /// Floor height of the cell the sprite is in
+    pub floor_height: f64,
     pub x: i16,
     pub distance: f64,
 }
 
 impl WindowSprite {
-    pub fn new(sprite_sheet_index: usize, sprite_sheet_y_offset: u32, x: i16, distance: f64) -> Self {
+    pub fn new(
+        sprite_sheet: String,
+        sprite_sheet_offset: Vector2<u32>,
+        placement: SpritePlacement,
+        opacity: f64,
+        floor_height: f64,
+        x: i16,
+        distance: f64,
+    ) -> Self {
         Self {
-            sprite_sheet_index,
-            sprite_sheet_y_offset,
+            sprite_sheet,
+            sprite_sheet_offset,
+            placement,
+            opacity,
+            floor_height,
             x,
             distance,
         }
@@ -31,29 +48,38 @@ pub fn get_sorted_window_sprites(raycasting: &Raycasting) -> Vec<WindowSprite> {
                 continue;
             }
         }
-        let angle_from_player = raycasting.player.get_angle_to(&sprite.position);
-        if angle_from_player > raycasting.player.horizontal_fov
-            || -raycasting.player.horizontal_fov > angle_from_player
+        let angle_from_player = raycasting.camera.get_angle_to(&sprite.position);
+        if angle_from_player > raycasting.camera.horizontal_fov
+            || -raycasting.camera.horizontal_fov > angle_from_player
         {
             continue;
         }
-        let distance = nalgebra::distance(&sprite.position, &raycasting.player.position)
+        let distance = nalgebra::distance(&sprite.position, &raycasting.camera.position)
             * angle_from_player.cos();
         if distance < 1e-10 {
             continue;
         }
         let projection_plane_distance = (raycasting.window.width() as f64 / 2.0)
-            / (raycasting.player.horizontal_fov / 2.0).tan();
+            / (raycasting.camera.horizontal_fov / 2.0).tan();
         let x_center_offset = -angle_from_player.tan() * projection_plane_distance;
         let x = (raycasting.window.width() as f64 / 2.0 + x_center_offset).round() as i16;
-        let sprite_sheet_y_offset = if let Some(angle) = sprite.angle {
-            let angle_to_sprite = get_normalized_radians_angle(raycasting.player.angle + angle_from_player);
-            let sprite_to_player_angle = get_normalized_radians_angle(angle_to_sprite + f64::consts::PI - angle);
-            raycasting.sprite_sheets[sprite.sprite_sheet_index].get_y_offset(sprite_to_player_angle)
-        } else {
-            0
-        };
-        window_sprites.push(WindowSprite::new(sprite.sprite_sheet_index, sprite_sheet_y_offset, x, distance));
+        let sprite_to_player_angle = sprite.angle.map(|angle| {
+            let angle_to_sprite =
+                get_normalized_radians_angle(raycasting.camera.angle + angle_from_player);
+            get_normalized_radians_angle(angle_to_sprite + f64::consts::PI - angle)
+        });
+        let sprite_sheet = raycasting.assets.sprite_sheet(&sprite.sprite_sheet);
+        let sprite_sheet_offset =
+            sprite_sheet.get_frame_offset(&sprite.animation, sprite_to_player_angle);
+        window_sprites.push(WindowSprite::new(
+            sprite.sprite_sheet.clone(),
+            sprite_sheet_offset,
+            sprite.placement.or(sprite_sheet.placement()),
+            sprite.opacity,
+            raycasting.map.cell_type_at(&sprite.position).floor_height,
+            x,
+            distance,
+        ));
     }
     window_sprites.sort_unstable_by(|a, b| b.distance.partial_cmp(&a.distance).unwrap());
     window_sprites
@@ -0,0 +1,473 @@
+use std::{collections::HashMap, error, fmt};
+
+use nalgebra::Point2;
+use serde::Deserialize;
+
+use crate::{
+    light::{Light, LightMap},
+    map::{CellType, Map, MapError},
+    sprite::{AnimationState, Sprite, SpritePlacement, VerticalAnchor, WallSprite},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpawnFile {
+    position: Point2<f64>,
+    /// Degrees
+    angle: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpriteFile {
+    position: Point2<f64>,
+    sprite_sheet: String,
+    /// Degrees, the sprite looking the same from every direction if missing
+    angle: Option<f64>,
+    /// Override the sprite sheet placement
+    width: Option<f64>,
+    height: Option<f64>,
+    vertical_anchor: Option<VerticalAnchor>,
+    vertical_offset: Option<f64>,
+    /// From 0 (invisible) to 1 (opaque), 1 if missing
+    opacity: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CellTypeFile {
+    floor_height: Option<f64>,
+    wall_height: Option<f64>,
+    #[serde(default)]
+    outdoor: bool,
+    #[serde(default)]
+    mirror: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WallSpriteFile {
+    start: Point2<f64>,
+    end: Point2<f64>,
+    sprite_sheet: String,
+    /// 0 if missing
+    bottom: Option<f64>,
+    /// 1 if missing
+    height: Option<f64>,
+    /// From 0 (invisible) to 1 (opaque), 1 if missing
+    opacity: Option<f64>,
+}
+
+/// Content of a level file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    /// Cell value used outside of the map
+    #[serde(default)]
+    void: u8,
+    spawn: SpawnFile,
+    /// Rows of cell values, one digit per cell
+    cells: Vec<String>,
+    /// Heights of the cells by value, the values missing here having the default ones
+    #[serde(default)]
+    cell_types: HashMap<String, CellTypeFile>,
+    #[serde(default)]
+    sprites: Vec<SpriteFile>,
+    #[serde(default)]
+    wall_sprites: Vec<WallSpriteFile>,
+    /// Light level everywhere, 1 being the unlit color
+    #[serde(default = "default_ambient_light")]
+    ambient_light: f64,
+    #[serde(default)]
+    lights: Vec<Light>,
+    /// Name of the panoramic texture seen above the outdoor cells
+    sky: Option<String>,
+    /// Pairs of linked portal cells, given as [x, y]
+    #[serde(default)]
+    portals: Vec<[[i64; 2]; 2]>,
+}
+
+fn default_ambient_light() -> f64 {
+    1.0
+}
+
+fn is_valid_height(height: f64) -> bool {
+    height >= 0.0 && height.is_finite()
+}
+
+fn is_valid_size(size: f64) -> bool {
+    size > 0.0 && size.is_finite()
+}
+
+fn is_valid_opacity(opacity: f64) -> bool {
+    (0.0..=1.0).contains(&opacity)
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Parse(toml::de::Error),
+    InvalidCell {
+        x: usize,
+        y: usize,
+        value: char,
+    },
+    InvalidCellType(String),
+    Map(MapError),
+    SpawnOutsideMap,
+    SpawnNotEmpty,
+    /// Index of a sprite with an invalid size, offset or opacity
+    InvalidSprite(usize),
+    /// Index of a wall sprite with an invalid height, bottom or opacity
+    InvalidWallSprite(usize),
+    /// Index of a light outside of the map or with an invalid intensity, radius or flicker
+    InvalidLight(usize),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Parse(e) => write!(f, "{}", e),
+            LevelError::InvalidCell { x, y, value } => {
+                write!(f, "cell ({}, {}) '{}' is not a digit", x, y, value)
+            }
+            LevelError::InvalidCellType(value) => write!(
+                f,
+                "cell type \"{}\" is not a digit with finite non-negative heights",
+                value
+            ),
+            LevelError::Map(e) => write!(f, "{}", e),
+            LevelError::SpawnOutsideMap => write!(f, "spawn is outside of the map"),
+            LevelError::SpawnNotEmpty => write!(f, "spawn is not in an empty cell"),
+            LevelError::InvalidSprite(index) => write!(
+                f,
+                "sprite {} does not have a positive size, a finite offset and an opacity from 0 to 1",
+                index
+            ),
+            LevelError::InvalidWallSprite(index) => write!(
+                f,
+                "wall sprite {} does not have a positive height, a finite bottom and an opacity \
+                 from 0 to 1",
+                index
+            ),
+            LevelError::InvalidLight(index) => write!(
+                f,
+                "light {} is not inside of the map with a finite intensity, a positive radius and \
+                 a flicker from 0 to 1",
+                index
+            ),
+        }
+    }
+}
+
+impl error::Error for LevelError {}
+
+impl From<toml::de::Error> for LevelError {
+    fn from(e: toml::de::Error) -> Self {
+        LevelError::Parse(e)
+    }
+}
+
+impl From<MapError> for LevelError {
+    fn from(e: MapError) -> Self {
+        LevelError::Map(e)
+    }
+}
+
+/// Map with the player spawn and the sprites placed on it
+#[derive(Debug)]
+pub struct Level {
+    pub map: Map,
+    pub spawn_position: Point2<f64>,
+    /// Radians
+    pub spawn_angle: f64,
+    pub sprites: Vec<Sprite>,
+    pub wall_sprites: Vec<WallSprite>,
+    pub light_map: LightMap,
+    /// Name of the panoramic texture seen above the outdoor cells
+    pub sky: Option<String>,
+}
+
+impl Level {
+    /// Parses the content of a level file
+    pub fn parse(content: &str) -> Result<Self, LevelError> {
+        let level_file: LevelFile = toml::from_str(content)?;
+        let cells = level_file
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .map(|(x, value)| {
+                        value
+                            .to_digit(10)
+                            .map(|digit| digit as u8)
+                            .ok_or(LevelError::InvalidCell { x, y, value })
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        let cell_types = level_file
+            .cell_types
+            .into_iter()
+            .map(|(value, cell_type_file)| {
+                let digit = match value.parse::<u8>() {
+                    Ok(digit) if digit < 10 => digit,
+                    _ => return Err(LevelError::InvalidCellType(value)),
+                };
+                let default = CellType::default_for(digit);
+                let cell_type = CellType {
+                    floor_height: cell_type_file.floor_height.unwrap_or(default.floor_height),
+                    wall_height: cell_type_file.wall_height.unwrap_or(default.wall_height),
+                    is_outdoor: cell_type_file.outdoor,
+                    is_mirror: cell_type_file.mirror,
+                };
+                if !is_valid_height(cell_type.floor_height)
+                    || !is_valid_height(cell_type.wall_height)
+                {
+                    return Err(LevelError::InvalidCellType(value));
+                }
+                Ok((digit, cell_type))
+            })
+            .collect::<Result<_, _>>()?;
+        let mut map = Map::with_cell_types(cells, level_file.void, cell_types)?;
+        for [[a_x, a_y], [b_x, b_y]] in level_file.portals {
+            map.link_portals((a_x, a_y), (b_x, b_y))?;
+        }
+        if !map.contains(&level_file.spawn.position) {
+            return Err(LevelError::SpawnOutsideMap);
+        }
+        if !map.is_empty_at(&level_file.spawn.position) {
+            return Err(LevelError::SpawnNotEmpty);
+        }
+        let sprites = level_file
+            .sprites
+            .into_iter()
+            .enumerate()
+            .map(|(id, sprite_file)| {
+                let opacity = sprite_file.opacity.unwrap_or(1.0);
+                if [sprite_file.width, sprite_file.height]
+                    .into_iter()
+                    .flatten()
+                    .any(|size| !is_valid_size(size))
+                    || sprite_file
+                        .vertical_offset
+                        .is_some_and(|offset| !offset.is_finite())
+                    || !is_valid_opacity(opacity)
+                {
+                    return Err(LevelError::InvalidSprite(id));
+                }
+                let mut sprite = Sprite::new(
+                    id as u32,
+                    sprite_file.position,
+                    &sprite_file.sprite_sheet,
+                    sprite_file.angle.map(f64::to_radians),
+                );
+                sprite.placement = SpritePlacement {
+                    width: sprite_file.width,
+                    height: sprite_file.height,
+                    vertical_anchor: sprite_file.vertical_anchor,
+                    vertical_offset: sprite_file.vertical_offset,
+                };
+                sprite.opacity = opacity;
+                Ok(sprite)
+            })
+            .collect::<Result<_, _>>()?;
+        let wall_sprites = level_file
+            .wall_sprites
+            .into_iter()
+            .enumerate()
+            .map(|(index, wall_sprite)| {
+                let wall_sprite = WallSprite {
+                    start: wall_sprite.start,
+                    end: wall_sprite.end,
+                    sprite_sheet: wall_sprite.sprite_sheet,
+                    bottom: wall_sprite.bottom.unwrap_or(0.0),
+                    height: wall_sprite.height.unwrap_or(1.0),
+                    opacity: wall_sprite.opacity.unwrap_or(1.0),
+                    animation: AnimationState::default(),
+                };
+                if !is_valid_size(wall_sprite.height)
+                    || !wall_sprite.bottom.is_finite()
+                    || !is_valid_opacity(wall_sprite.opacity)
+                {
+                    return Err(LevelError::InvalidWallSprite(index));
+                }
+                Ok(wall_sprite)
+            })
+            .collect::<Result<_, _>>()?;
+        if let Some(index) = level_file.lights.iter().position(|light| {
+            !(map.contains(&light.position)
+                && light.intensity.is_finite()
+                && is_valid_size(light.radius)
+                && (0.0..=1.0).contains(&light.flicker))
+        }) {
+            return Err(LevelError::InvalidLight(index));
+        }
+        let light_map = LightMap::new(&map, level_file.ambient_light, level_file.lights);
+        Ok(Self {
+            map,
+            spawn_position: level_file.spawn.position,
+            spawn_angle: level_file.spawn.angle.to_radians(),
+            sprites,
+            wall_sprites,
+            light_map,
+            sky: level_file.sky,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        let level = Level::parse(
+            r#"
+            spawn = { position = [1.5, 1.5], angle = 90.0 }
+            cells = [
+                "111",
+                "131",
+                "111",
+            ]
+
+            [cell_types]
+            3 = { floor_height = 0.25, wall_height = 0.0, outdoor = true }
+
+            [[sprites]]
+            position = [1.5, 1.2]
+            sprite_sheet = "penguin"
+            height = 0.25
+            vertical_anchor = "ceiling"
+
+            [[wall_sprites]]
+            start = [1.0, 2.0]
+            end = [2.0, 2.0]
+            sprite_sheet = "fence"
+            "#,
+        )
+        .unwrap();
+        assert!(level.map.is_empty_at(&Point2::new(1.5, 1.5)));
+        assert_eq!(level.map.cell_type(1, 1).floor_height, 0.25);
+        assert!(level.map.cell_type(1, 1).is_outdoor);
+        assert_eq!(level.map.get(2, 1), 1);
+        assert!((level.spawn_angle - 90_f64.to_radians()).abs() < 1e-10);
+        assert_eq!(level.sprites.len(), 1);
+        assert_eq!(level.sprites[0].sprite_sheet, "penguin");
+        assert_eq!(level.sprites[0].angle, None);
+        assert_eq!(level.sprites[0].placement.height, Some(0.25));
+        assert_eq!(
+            level.sprites[0].placement.vertical_anchor,
+            Some(VerticalAnchor::Ceiling)
+        );
+        assert_eq!(level.sprites[0].placement.width, None);
+        assert_eq!(level.wall_sprites.len(), 1);
+        assert_eq!(level.wall_sprites[0].height, 1.0);
+
+        let error = Level::parse(
+            r#"
+            spawn = { position = [1.5, 1.5], angle = 0.0 }
+            cells = ["111", "1x1", "111"]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            LevelError::InvalidCell {
+                x: 1,
+                y: 1,
+                value: 'x'
+            }
+        ));
+
+        let error = Level::parse(
+            r#"
+            spawn = { position = [0.5, 0.5], angle = 0.0 }
+            cells = ["111", "101", "111"]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, LevelError::SpawnNotEmpty));
+
+        let error = Level::parse(
+            r#"
+            void = 0
+            spawn = { position = [-5.0, 1.5], angle = 0.0 }
+            cells = ["111", "101", "111"]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, LevelError::SpawnOutsideMap));
+
+        let error = Level::parse(
+            r#"
+            spawn = { position = [1.5, 1.5], angle = 0.0 }
+            cells = ["111", "101", "111"]
+            cell_types = { 12 = { wall_height = 0.5 } }
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, LevelError::InvalidCellType(_)));
+
+        for cell_type in [
+            "wall_height = nan",
+            "floor_height = inf",
+            "wall_height = -0.5",
+        ] {
+            let error = Level::parse(&format!(
+                r#"
+                spawn = {{ position = [1.5, 1.5], angle = 0.0 }}
+                cells = ["111", "101", "111"]
+                cell_types = {{ 2 = {{ {} }} }}
+                "#,
+                cell_type
+            ))
+            .unwrap_err();
+            assert!(matches!(error, LevelError::InvalidCellType(_)));
+        }
+
+        let error = Level::parse(
+            r#"
+            spawn = { position = [1.5, 1.5], angle = 0.0 }
+            cells = ["111", "101", "111"]
+            sprites = [
+                { position = [1.5, 1.5], sprite_sheet = "penguin" },
+                { position = [1.5, 1.5], sprite_sheet = "penguin", height = -1.0 },
+            ]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, LevelError::InvalidSprite(1)));
+
+        let error = Level::parse(
+            r#"
+            spawn = { position = [1.5, 1.5], angle = 0.0 }
+            cells = ["111", "101", "111"]
+            wall_sprites = [
+                { start = [1.0, 1.0], end = [2.0, 1.0], sprite_sheet = "fence", opacity = nan },
+            ]
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, LevelError::InvalidWallSprite(0)));
+
+        for light in [
+            "position = [1.5, 1.5], radius = -1.0",
+            "position = [1.5, 1.5], intensity = nan",
+            "position = [1.5, 1.5], flicker = 1.5",
+            "position = [1e9, 1.5], radius = 1e9",
+        ] {
+            let error = Level::parse(&format!(
+                r#"
+                spawn = {{ position = [1.5, 1.5], angle = 0.0 }}
+                cells = ["111", "101", "111"]
+                lights = [{{ position = [1.5, 1.5] }}, {{ {} }}]
+                "#,
+                light
+            ))
+            .unwrap_err();
+            assert!(matches!(error, LevelError::InvalidLight(1)));
+        }
+    }
+}
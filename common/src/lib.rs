@@ -1,17 +1,17 @@
 use std::f64;
 
+pub mod level;
+pub mod light;
+pub mod map;
 pub mod sprite;
+pub mod timestep;
 
-pub static MAP: [[u8; 8]; 8] = [
-    [1, 1, 1, 1, 1, 1, 1, 1],
-    [1, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 2, 0, 1],
-    [1, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 0, 1],
-    [1, 1, 1, 1, 1, 1, 1, 1],
-];
+/// Longest skin name a client can send to the server, in bytes
+pub const MAX_SKIN_LENGTH: usize = 64;
+
+/// Largest UDP datagram payload, messages being received in buffers of this size so that they are
+/// never truncated
+pub const MAX_DATAGRAM_SIZE: usize = 65507;
 
 /// Get angle in [0; tau[
 pub fn get_normalized_radians_angle(mut angle: f64) -> f64 {
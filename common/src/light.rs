@@ -0,0 +1,164 @@
+use std::f64;
+
+use nalgebra::Point2;
+use serde::Deserialize;
+
+use crate::map::Map;
+
+/// Distance between the points checked for walls between a light and a cell
+const VISIBILITY_STEP: f64 = 0.05;
+
+/// Point light, eg. a torch or a lamp
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Light {
+    pub position: Point2<f64>,
+    /// Light added at the light position
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+    /// Distance at which the light fades out
+    #[serde(default = "default_radius")]
+    pub radius: f64,
+    /// From 0 (steady) to 1, how much the intensity randomly drops over time
+    #[serde(default)]
+    pub flicker: f64,
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+fn default_radius() -> f64 {
+    4.0
+}
+
+impl Light {
+    /// Get the intensity multiplier of the light at `time` in seconds, in [1 - flicker; 1]
+    fn get_flicker_factor(&self, index: usize, time: f64) -> f64 {
+        // Product of sines with unrelated frequencies, so that it does not look periodic
+        let phase = index as f64 * 1.7;
+        let noise = (time * 7.3 + phase).sin() * (time * 13.1 + phase * 2.3).sin();
+        1.0 - self.flicker * (0.5 + 0.5 * noise)
+    }
+}
+
+/// Light level of each map cell, 1 being the unlit color
+#[derive(Debug, Clone)]
+pub struct LightMap {
+    width: usize,
+    height: usize,
+    /// Light level everywhere, including outside of the map
+    ambient: f64,
+    lights: Vec<Light>,
+    /// Light added by each light to each cell at full intensity
+    contributions: Vec<Vec<f64>>,
+    levels: Vec<f64>,
+    time: f64,
+}
+
+/// Whether no wall stands between `from` and the cell containing `to`
+fn is_visible(map: &Map, from: &Point2<f64>, to: &Point2<f64>) -> bool {
+    let target_cell = (to.x.floor(), to.y.floor());
+    let steps = (nalgebra::distance(from, to) / VISIBILITY_STEP).ceil() as usize;
+    (1..steps).all(|step| {
+        let point = from + (to - from) * (step as f64 / steps as f64);
+        (point.x.floor(), point.y.floor()) == target_cell || map.is_empty_at(&point)
+    })
+}
+
+impl LightMap {
+    /// Precomputes the light that `lights` bring to the center of each cell of `map`, walls
+    /// casting shadows
+    pub fn new(map: &Map, ambient: f64, lights: Vec<Light>) -> Self {
+        let contributions = lights
+            .iter()
+            .map(|light| {
+                (0..map.height())
+                    .flat_map(|y| (0..map.width()).map(move |x| (x, y)))
+                    .map(|(x, y)| {
+                        let center = Point2::new(x as f64 + 0.5, y as f64 + 0.5);
+                        let distance = nalgebra::distance(&light.position, &center);
+                        if distance >= light.radius || !is_visible(map, &light.position, &center) {
+                            0.0
+                        } else {
+                            light.intensity * (1.0 - distance / light.radius)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut light_map = Self {
+            width: map.width(),
+            height: map.height(),
+            ambient,
+            lights,
+            contributions,
+            levels: vec![ambient; map.width() * map.height()],
+            time: 0.0,
+        };
+        light_map.update(0.0);
+        light_map
+    }
+
+    /// Advance the time by `delta_time` seconds and update the levels of the flickering lights
+    pub fn update(&mut self, delta_time: f64) {
+        self.time += delta_time;
+        self.levels.fill(self.ambient);
+        for (index, (light, contribution)) in
+            self.lights.iter().zip(&self.contributions).enumerate()
+        {
+            let factor = light.get_flicker_factor(index, self.time);
+            for (level, cell_contribution) in self.levels.iter_mut().zip(contribution) {
+                *level += factor * cell_contribution;
+            }
+        }
+    }
+
+    /// Light level everywhere, including outside of the map
+    pub fn ambient(&self) -> f64 {
+        self.ambient
+    }
+
+    /// Get the light level of the cell at (x, y), or the ambient one if it is outside of the map
+    pub fn get(&self, x: i64, y: i64) -> f64 {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return self.ambient;
+        }
+        self.levels[y as usize * self.width + x as usize]
+    }
+
+    /// Get the light level of the cell containing `position`
+    pub fn get_at(&self, position: &Point2<f64>) -> f64 {
+        self.get(position.x.floor() as i64, position.y.floor() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_map() {
+        let map = Map::new(
+            vec![
+                vec![1, 1, 1, 1, 1],
+                vec![1, 0, 1, 0, 1],
+                vec![1, 1, 1, 1, 1],
+            ],
+            0,
+        )
+        .unwrap();
+        let light = Light {
+            position: Point2::new(1.5, 1.5),
+            intensity: 1.0,
+            radius: 4.0,
+            flicker: 0.0,
+        };
+        let light_map = LightMap::new(&map, 0.2, vec![light]);
+        assert!((light_map.get(1, 1) - 1.2).abs() < 1e-10);
+        // The wall itself is lit, but not what is behind it
+        assert!((light_map.get(2, 1) - 0.95).abs() < 1e-10);
+        assert!((light_map.get(3, 1) - 0.2).abs() < 1e-10);
+        assert_eq!(light_map.get(-1, 0), 0.2);
+    }
+}
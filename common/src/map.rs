@@ -0,0 +1,308 @@
+use std::{collections::HashMap, error, fmt};
+
+use nalgebra::Point2;
+
+/// Value of an empty cell
+pub const EMPTY: u8 = 0;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapError {
+    Empty,
+    NotRectangular {
+        row: usize,
+    },
+    /// Coordinates (x, y) of the border cells through which the void can be reached
+    UnclosedBorder(Vec<(usize, usize)>),
+    /// Coordinates (x, y) of a portal cell that is outside of the map, a wall or already linked
+    InvalidPortal((i64, i64)),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "map is empty"),
+            MapError::NotRectangular { row } => {
+                write!(
+                    f,
+                    "map row {} does not have the same width as the first row",
+                    row
+                )
+            }
+            MapError::UnclosedBorder(cells) => {
+                write!(f, "map border is not closed at")?;
+                for (x, y) in cells {
+                    write!(f, " ({}, {})", x, y)?;
+                }
+                Ok(())
+            }
+            MapError::InvalidPortal((x, y)) => write!(
+                f,
+                "portal cell ({}, {}) is outside of the map, a wall or already linked",
+                x, y
+            ),
+        }
+    }
+}
+
+impl error::Error for MapError {}
+
+/// Heights of the cells with a given value, walls being 1 unit high by default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellType {
+    /// Height of the ground of the cell
+    pub floor_height: f64,
+    /// Height of the wall standing on the ground of the cell, 0 if the cell can be walked on
+    pub wall_height: f64,
+    /// Show the sky instead of the ceiling above the cell
+    pub is_outdoor: bool,
+    /// Reflect the view on the sides of the cell
+    pub is_mirror: bool,
+}
+
+impl CellType {
+    /// Get the cell type of `value` when the map does not define it
+    pub fn default_for(value: u8) -> Self {
+        Self {
+            floor_height: 0.0,
+            wall_height: if value == EMPTY { 0.0 } else { 1.0 },
+            is_outdoor: false,
+            is_mirror: false,
+        }
+    }
+
+    pub fn is_wall(&self) -> bool {
+        self.wall_height > 0.0
+    }
+
+    /// Height of the top of the cell
+    pub fn top(&self) -> f64 {
+        self.floor_height + self.wall_height
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Map {
+    cells: Vec<Vec<u8>>,
+    width: usize,
+    height: usize,
+    void: u8,
+    cell_types: HashMap<u8, CellType>,
+    /// Highest top of the cells, including the void
+    max_top: f64,
+    /// Cell linked to each portal cell, entering one leading to the same place in the other
+    portals: HashMap<(i64, i64), (i64, i64)>,
+}
+
+impl Map {
+    /// Creates a map, `void` being the cell value used outside of the map.
+    ///
+    /// If `void` is [`EMPTY`], the border cells must all be walls.
+    pub fn new(cells: Vec<Vec<u8>>, void: u8) -> Result<Self, MapError> {
+        Self::with_cell_types(cells, void, HashMap::new())
+    }
+
+    /// Creates a map whose cell values have the heights of `cell_types`, the other values having
+    /// the default ones.
+    ///
+    /// If `void` is not a wall, the border cells must all be walls.
+    pub fn with_cell_types(
+        cells: Vec<Vec<u8>>,
+        void: u8,
+        cell_types: HashMap<u8, CellType>,
+    ) -> Result<Self, MapError> {
+        let width = cells.first().map_or(0, Vec::len);
+        if width == 0 {
+            return Err(MapError::Empty);
+        }
+        if let Some(row) = cells.iter().position(|row| row.len() != width) {
+            return Err(MapError::NotRectangular { row });
+        }
+        let height = cells.len();
+        let mut map = Self {
+            cells,
+            width,
+            height,
+            void,
+            cell_types,
+            max_top: 0.0,
+            portals: HashMap::new(),
+        };
+        map.max_top = map
+            .cells
+            .iter()
+            .flatten()
+            .chain([&void])
+            .map(|&value| map.get_cell_type(value).top())
+            .fold(0.0, f64::max);
+        if !map.get_cell_type(void).is_wall() {
+            let unclosed_border_cells: Vec<_> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| x == 0 || y == 0 || x == width - 1 || y == height - 1)
+                .filter(|&(x, y)| !map.get_cell_type(map.cells[y][x]).is_wall())
+                .collect();
+            if !unclosed_border_cells.is_empty() {
+                return Err(MapError::UnclosedBorder(unclosed_border_cells));
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the cell value at (x, y), or the void value if it is outside of the map
+    pub fn get(&self, x: i64, y: i64) -> u8 {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return self.void;
+        }
+        self.cells[y as usize][x as usize]
+    }
+
+    /// Get the value of the cell containing `position`
+    pub fn get_at(&self, position: &Point2<f64>) -> u8 {
+        self.get(position.x.floor() as i64, position.y.floor() as i64)
+    }
+
+    /// Get the cell type of the cell value `value`
+    pub fn get_cell_type(&self, value: u8) -> CellType {
+        self.cell_types
+            .get(&value)
+            .copied()
+            .unwrap_or_else(|| CellType::default_for(value))
+    }
+
+    /// Get the cell type of the cell at (x, y), or of the void if it is outside of the map
+    pub fn cell_type(&self, x: i64, y: i64) -> CellType {
+        self.get_cell_type(self.get(x, y))
+    }
+
+    /// Get the cell type of the cell containing `position`
+    pub fn cell_type_at(&self, position: &Point2<f64>) -> CellType {
+        self.get_cell_type(self.get_at(position))
+    }
+
+    /// Highest top of the cells
+    pub fn max_top(&self) -> f64 {
+        self.max_top
+    }
+
+    /// Link the cells `a` and `b` given as (x, y), so that entering one leads to the same place in
+    /// the other
+    pub fn link_portals(&mut self, a: (i64, i64), b: (i64, i64)) -> Result<(), MapError> {
+        for (x, y) in [a, b] {
+            let is_inside = x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64;
+            if a == b
+                || !is_inside
+                || self.cell_type(x, y).is_wall()
+                || self.portals.contains_key(&(x, y))
+            {
+                return Err(MapError::InvalidPortal((x, y)));
+            }
+        }
+        self.portals.insert(a, b);
+        self.portals.insert(b, a);
+        Ok(())
+    }
+
+    /// Get the cell linked to the portal cell at (x, y), if it is one
+    pub fn get_portal(&self, x: i64, y: i64) -> Option<(i64, i64)> {
+        self.portals.get(&(x, y)).copied()
+    }
+
+    /// Whether `position` is inside of the map, rather than in the void around it
+    pub fn contains(&self, position: &Point2<f64>) -> bool {
+        (0.0..self.width as f64).contains(&position.x)
+            && (0.0..self.height as f64).contains(&position.y)
+    }
+
+    /// Whether the cell containing `position` can be walked on
+    pub fn is_empty_at(&self, position: &Point2<f64>) -> bool {
+        !self.cell_type_at(position).is_wall()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_validation() {
+        assert_eq!(Map::new(vec![], EMPTY).unwrap_err(), MapError::Empty);
+        assert_eq!(
+            Map::new(vec![vec![1, 1], vec![1]], 1).unwrap_err(),
+            MapError::NotRectangular { row: 1 }
+        );
+        assert_eq!(
+            Map::new(vec![vec![1, 1, 1], vec![0, 0, 1], vec![1, 1, 1]], EMPTY).unwrap_err(),
+            MapError::UnclosedBorder(vec![(0, 1)])
+        );
+        assert!(Map::new(vec![vec![1, 1, 1], vec![0, 0, 1], vec![1, 1, 1]], 1).is_ok());
+        assert!(Map::new(vec![vec![1, 1, 1], vec![1, 0, 1], vec![1, 1, 1]], EMPTY).is_ok());
+    }
+
+    #[test]
+    fn test_map_get_outside() {
+        let map = Map::new(vec![vec![0, 2], vec![0, 0]], 1).unwrap();
+        assert_eq!(map.get(1, 0), 2);
+        assert_eq!(map.get(-1, 0), 1);
+        assert_eq!(map.get(0, 2), 1);
+        assert_eq!(map.get_at(&Point2::new(-0.5, 0.5)), 1);
+        assert!(map.is_empty_at(&Point2::new(0.5, 1.5)));
+    }
+
+    #[test]
+    fn test_map_cell_types() {
+        let step = CellType {
+            floor_height: 0.25,
+            wall_height: 0.0,
+            is_outdoor: false,
+            is_mirror: false,
+        };
+        let pillar = CellType {
+            floor_height: 0.0,
+            wall_height: 2.0,
+            is_outdoor: false,
+            is_mirror: false,
+        };
+        let cells = vec![vec![1, 1, 1], vec![1, 3, 1], vec![4, 1, 1]];
+        let map = Map::with_cell_types(
+            cells.clone(),
+            EMPTY,
+            HashMap::from([(3, step), (4, pillar)]),
+        )
+        .unwrap();
+        assert!(map.is_empty_at(&Point2::new(1.5, 1.5)));
+        assert_eq!(map.cell_type(1, 1).top(), 0.25);
+        assert_eq!(map.cell_type(0, 0), CellType::default_for(1));
+        assert_eq!(map.max_top(), 2.0);
+        assert_eq!(
+            Map::with_cell_types(cells, EMPTY, HashMap::from([(4, step)])).unwrap_err(),
+            MapError::UnclosedBorder(vec![(0, 2)])
+        );
+    }
+
+    #[test]
+    fn test_map_portals() {
+        let mut map = Map::new(
+            vec![vec![1, 1, 1, 1], vec![1, 0, 0, 1], vec![1, 1, 1, 1]],
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            map.link_portals((1, 1), (0, 0)),
+            Err(MapError::InvalidPortal((0, 0)))
+        );
+        map.link_portals((1, 1), (2, 1)).unwrap();
+        assert_eq!(map.get_portal(2, 1), Some((1, 1)));
+        assert_eq!(map.get_portal(0, 1), None);
+        assert_eq!(
+            map.link_portals((2, 1), (1, 1)),
+            Err(MapError::InvalidPortal((2, 1)))
+        );
+    }
+}
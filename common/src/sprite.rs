@@ -1,21 +1,108 @@
 use nalgebra::Point2;
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnimationState {
+    /// Name of the sprite sheet animation, its first animation being used if it has none with
+    /// this name
+    pub name: String,
+    /// Seconds elapsed since the start of the animation
+    pub time: f64,
+}
+
+impl AnimationState {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            time: 0.0,
+        }
+    }
+
+    /// Switch to the `name` animation, restarting it only if it is not already playing
+    pub fn play(&mut self, name: &str) {
+        if self.name != name {
+            *self = Self::new(name);
+        }
+    }
+}
+
+/// What the sprite anchor is vertically placed relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerticalAnchor {
+    /// Stand on the floor, eg. pickups
+    Floor,
+    /// Hang from the ceiling, eg. lamps
+    Ceiling,
+}
+
+/// World size and vertical placement of a sprite, missing values being taken from elsewhere
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpritePlacement {
+    /// World width of a frame, following the frame aspect ratio if missing
+    pub width: Option<f64>,
+    /// World height of a frame, following the frame aspect ratio if missing
+    pub height: Option<f64>,
+    pub vertical_anchor: Option<VerticalAnchor>,
+    /// Distance of the anchor from the floor or the ceiling
+    pub vertical_offset: Option<f64>,
+}
+
+impl SpritePlacement {
+    /// Fill the missing values with the ones of `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            width: self.width.or(other.width),
+            height: self.height.or(other.height),
+            vertical_anchor: self.vertical_anchor.or(other.vertical_anchor),
+            vertical_offset: self.vertical_offset.or(other.vertical_offset),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Sprite {
     pub id: u32,
     pub position: Point2<f64>,
-    pub sprite_sheet_index: usize,
+    /// Name of the sprite sheet asset
+    pub sprite_sheet: String,
     pub angle: Option<f64>,
+    pub animation: AnimationState,
+    /// Overrides the placement of the sprite sheet
+    pub placement: SpritePlacement,
+    /// From 0 (invisible) to 1 (opaque), multiplying the alpha of the sprite sheet pixels
+    pub opacity: f64,
 }
 
 impl Sprite {
-    pub fn new(id: u32, position: Point2<f64>, image_index: usize, angle: Option<f64>) -> Self {
+    pub fn new(id: u32, position: Point2<f64>, sprite_sheet: &str, angle: Option<f64>) -> Self {
         Self {
             id,
             position,
-            sprite_sheet_index: image_index,
+            sprite_sheet: sprite_sheet.to_owned(),
             angle,
+            animation: AnimationState::default(),
+            placement: SpritePlacement::default(),
+            opacity: 1.0,
         }
     }
 }
+
+/// Sprite laid on a line segment of the world instead of facing the viewer, eg. signs, bars,
+/// fences
+#[derive(Debug, Clone)]
+pub struct WallSprite {
+    /// Point where the left of the frame is, the frame being mirrored when seen from its back
+    pub start: Point2<f64>,
+    /// Point where the right of the frame is
+    pub end: Point2<f64>,
+    /// Name of the sprite sheet asset
+    pub sprite_sheet: String,
+    /// Height of the bottom of the frame above the floor
+    pub bottom: f64,
+    /// World height of the frame
+    pub height: f64,
+    /// From 0 (invisible) to 1 (opaque), multiplying the alpha of the sprite sheet pixels
+    pub opacity: f64,
+    pub animation: AnimationState,
+}
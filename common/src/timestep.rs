@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+/// Maximum time that can be accumulated, to avoid an ever growing simulation backlog when the
+/// simulation steps are slower than real time
+const MAX_ACCUMULATED_TIME: f64 = 0.25;
+
+/// Fixed timestep clock: real time is accumulated and then consumed in steps of constant
+/// duration, making the simulation independent of the frame rate and reproducible
+#[derive(Debug)]
+pub struct FixedTimestep {
+    step: f64,
+    accumulated_time: f64,
+    last_instant: Instant,
+}
+
+impl FixedTimestep {
+    /// Creates a clock with steps of `step` seconds
+    pub fn new(step: f64) -> Self {
+        Self {
+            step,
+            accumulated_time: 0.0,
+            last_instant: Instant::now(),
+        }
+    }
+
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Accumulate the real time elapsed since the last call
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        self.accumulate((now - self.last_instant).as_secs_f64());
+        self.last_instant = now;
+    }
+
+    pub fn accumulate(&mut self, seconds: f64) {
+        self.accumulated_time = f64::min(self.accumulated_time + seconds, MAX_ACCUMULATED_TIME);
+    }
+
+    /// Consume a step of the accumulated time, returns `false` if there is not enough of it
+    pub fn consume_step(&mut self) -> bool {
+        if self.accumulated_time < self.step {
+            return false;
+        }
+        self.accumulated_time -= self.step;
+        true
+    }
+
+    /// Get the fraction of a step remaining in the accumulated time, in [0; 1[, used to
+    /// interpolate between the last two simulation states
+    pub fn alpha(&self) -> f64 {
+        self.accumulated_time / self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_timestep() {
+        let mut timestep = FixedTimestep::new(0.1);
+        timestep.accumulate(0.25);
+        assert!(timestep.consume_step());
+        assert!(timestep.consume_step());
+        assert!(!timestep.consume_step());
+        assert!((timestep.alpha() - 0.5).abs() < 1e-10);
+
+        timestep.accumulate(10.0);
+        let mut steps = 0;
+        while timestep.consume_step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+    }
+}
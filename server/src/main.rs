@@ -3,10 +3,13 @@ use std::{
     collections::HashMap,
     io,
     net::{SocketAddr, ToSocketAddrs, UdpSocket},
-    time::{Duration, Instant},
 };
 
-use common::sprite::Sprite;
+use common::{sprite::Sprite, timestep::FixedTimestep, MAX_DATAGRAM_SIZE, MAX_SKIN_LENGTH};
+use nalgebra::Point2;
+
+/// Most clients connected at once, so that the list of their sprites fits in a datagram
+const MAX_CLIENTS: usize = 256;
 
 /// raycasting-term server
 #[derive(Parser, Debug)]
@@ -19,6 +22,8 @@ struct Args {
 struct Server {
     socket: UdpSocket,
     clients: HashMap<SocketAddr, Sprite>,
+    /// Client positions at the last tick, used to know which ones are walking
+    last_tick_positions: HashMap<SocketAddr, Point2<f64>>,
     next_id: u32,
 }
 
@@ -29,16 +34,33 @@ impl Server {
         Ok(Self {
             socket,
             clients: HashMap::new(),
+            last_tick_positions: HashMap::new(),
             next_id: 0,
         })
     }
 
     fn handle_message(&mut self, buf: &[u8], addr: SocketAddr) -> std::io::Result<()> {
-        let (position, angle) = bincode::deserialize(buf).unwrap();
+        let (position, angle, skin): (Point2<f64>, f64, String) = match bincode::deserialize(buf) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Ignoring invalid message from {}: {}", addr, e);
+                return Ok(());
+            }
+        };
+        if skin.len() > MAX_SKIN_LENGTH {
+            eprintln!("Ignoring message from {} with a too long skin name", addr);
+            return Ok(());
+        }
+        let is_full = self.clients.len() >= MAX_CLIENTS;
         match self.clients.get_mut(&addr) {
+            None if is_full => {
+                eprintln!("Ignoring client {}, the server is full", addr);
+            }
             None => {
-                self.clients
-                    .insert(addr, Sprite::new(self.next_id, position, 0, Some(angle)));
+                self.clients.insert(
+                    addr,
+                    Sprite::new(self.next_id, position, &skin, Some(angle)),
+                );
                 self.socket
                     .send_to(&bincode::serialize(&self.next_id).unwrap(), addr)?;
                 // TODO: resend until id is received
@@ -47,15 +69,28 @@ impl Server {
             Some(sprite) => {
                 sprite.position = position;
                 sprite.angle = Some(angle);
+                sprite.sprite_sheet = skin;
             }
         }
         Ok(())
     }
 
+    fn update_animations(&mut self) {
+        for (addr, sprite) in &mut self.clients {
+            match self.last_tick_positions.insert(*addr, sprite.position) {
+                Some(last_tick_position) if last_tick_position != sprite.position => {
+                    sprite.animation.play("walk");
+                }
+                _ => sprite.animation.play("idle"),
+            }
+        }
+    }
+
     fn run(&mut self) -> io::Result<()> {
-        let mut buf = [0; 128];
-        let mut time = Instant::now();
-        let duration = Duration::from_secs_f64(1.0 / 30.0);
+        const TICK_DURATION: f64 = 1.0 / 30.0;
+
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        let mut timestep = FixedTimestep::new(TICK_DURATION);
 
         println!("Server running on {}", self.socket.local_addr()?);
         loop { // TODO: remove clients if their last update exceeds a time limit
@@ -64,13 +99,18 @@ impl Server {
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
                 Err(e) => Err(e),
             }?;
-            if time.elapsed() >= duration {
+            timestep.update();
+            let mut has_ticked = false;
+            while timestep.consume_step() {
+                has_ticked = true;
+            }
+            if has_ticked {
+                self.update_animations();
                 let serialized_sprites =
                     &bincode::serialize(&Vec::from_iter(self.clients.values())).unwrap();
                 for addr in self.clients.keys() {
                     self.socket.send_to(serialized_sprites, addr)?;
                 }
-                time = Instant::now();
             }
         }
     }